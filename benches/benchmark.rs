@@ -1,5 +1,9 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use lru_cache::linked_list::{DLL, cellist, veclist};
+use lru_cache::linked_list::{DLL, cellist, ptrlist, veclist};
+use lru_cache::lru::bufferedlru::BufferedLru;
+use lru_cache::lru::shardedlru::ShardedLru;
+use lru_cache::lru::synclru::SyncLru;
+use std::thread;
 
 fn add_then_mutate<L: DLL<usize>>(size: usize) {
   let mut list = L::new();
@@ -7,7 +11,7 @@ fn add_then_mutate<L: DLL<usize>>(size: usize) {
     list.push_back(i);
   }
 
-  for i in list.iter_mut() {
+  for mut i in list.iter_mut() {
     *i += 10;
   }
 
@@ -54,7 +58,57 @@ fn add_then_move_front<L: DLL<usize>>(size: usize) {
   assert_eq!(list.pop_front(), None);
 }
 
+// Every thread does the same mix of reads and writes over a key space
+// that fits in the cache, so after warm-up it is mostly lock traffic.
+fn concurrent_get_put<F: Fn(usize) + Sync>(threads: usize, ops: usize, access: F) {
+  thread::scope(|s| {
+    for t in 0..threads {
+      let access = &access;
+      s.spawn(move || {
+        for i in 0..ops {
+          access((t * 7919 + i * 31) % 512);
+        }
+      });
+    }
+  });
+}
 
+fn concurrent_benchmark(c: &mut Criterion) {
+  for threads in [1, 2, 4, 8, 16, 32] {
+    let sync = SyncLru::new(1024);
+    c.bench_function(
+      &format!("Sync Get Put {threads} threads"), |b| b.iter(||
+        concurrent_get_put(threads, black_box(1000), |key| {
+          if sync.get(&key).is_none() {
+            sync.put(key, key);
+          }
+        })
+      )
+    );
+
+    let sharded = ShardedLru::with_shards(1024, 16);
+    c.bench_function(
+      &format!("Sharded Get Put {threads} threads"), |b| b.iter(||
+        concurrent_get_put(threads, black_box(1000), |key| {
+          if sharded.get(&key).is_none() {
+            sharded.put(key, key);
+          }
+        })
+      )
+    );
+
+    let buffered = BufferedLru::new(1024);
+    c.bench_function(
+      &format!("Buffered Get Put {threads} threads"), |b| b.iter(||
+        concurrent_get_put(threads, black_box(1000), |key| {
+          if buffered.get(&key).is_none() {
+            buffered.put(key, key);
+          }
+        })
+      )
+    );
+  }
+}
 
 fn criterion_benchmark(c: &mut Criterion) {
   c.bench_function(
@@ -63,10 +117,15 @@ fn criterion_benchmark(c: &mut Criterion) {
     )
   );
   c.bench_function(
-    "Vec Add Mutate 1000", |b| b.iter(||
+    "Cell Add Mutate 1000", |b| b.iter(||
       add_then_mutate::<cellist::CellLinkedList<usize>>(black_box(1000))
     )
   );
+  c.bench_function(
+    "Ptr Add Mutate 1000", |b| b.iter(||
+      add_then_mutate::<ptrlist::PtrLinkedList<usize>>(black_box(1000))
+    )
+  );
 
   c.bench_function(
     "Vec Add Move 1000", |b| b.iter(||
@@ -78,6 +137,11 @@ fn criterion_benchmark(c: &mut Criterion) {
       add_then_move_back::<cellist::CellLinkedList<usize>>(black_box(1000))
     )
   );
+  c.bench_function(
+    "Ptr Add Move 1000", |b| b.iter(||
+      add_then_move_back::<ptrlist::PtrLinkedList<usize>>(black_box(1000))
+    )
+  );
 
   c.bench_function(
     "Vec Add Move Front 1000", |b| b.iter(||
@@ -89,7 +153,48 @@ fn criterion_benchmark(c: &mut Criterion) {
       add_then_move_front::<cellist::CellLinkedList<usize>>(black_box(1000))
     )
   );
+  c.bench_function(
+    "Ptr Add Move Front 1000", |b| b.iter(||
+      add_then_move_front::<ptrlist::PtrLinkedList<usize>>(black_box(1000))
+    )
+  );
+}
+
+// Same work on each index type, with enough nodes that the spine
+// doesn't fit in cache, so smaller nodes should show up.
+fn index_benchmark(c: &mut Criterion) {
+  c.bench_function(
+    "usize Add Move 50000", |b| b.iter(||
+      add_then_move_back::<veclist::VectorLinkedList<usize>>(black_box(50_000))
+    )
+  );
+  c.bench_function(
+    "u32 Add Move 50000", |b| b.iter(||
+      add_then_move_back::<veclist::CompactLinkedList<usize>>(black_box(50_000))
+    )
+  );
+  c.bench_function(
+    "u16 Add Move 50000", |b| b.iter(||
+      add_then_move_back::<veclist::SmallLinkedList<usize>>(black_box(50_000))
+    )
+  );
+
+  c.bench_function(
+    "usize Add Mutate 50000", |b| b.iter(||
+      add_then_mutate::<veclist::VectorLinkedList<usize>>(black_box(50_000))
+    )
+  );
+  c.bench_function(
+    "u32 Add Mutate 50000", |b| b.iter(||
+      add_then_mutate::<veclist::CompactLinkedList<usize>>(black_box(50_000))
+    )
+  );
+  c.bench_function(
+    "u16 Add Mutate 50000", |b| b.iter(||
+      add_then_mutate::<veclist::SmallLinkedList<usize>>(black_box(50_000))
+    )
+  );
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, concurrent_benchmark, index_benchmark);
 criterion_main!(benches);
\ No newline at end of file
@@ -0,0 +1,26 @@
+use core::fmt;
+
+// What the try_ methods hand back instead of panicking or quietly doing
+// nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+  // The pointer's node has been removed, or the pointer is from another
+  // list.
+  StalePointer,
+  // There was nothing to take out.
+  EmptyList,
+  // The list can't hold any more nodes.
+  CapacityExceeded,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::StalePointer => write!(f, "pointer doesn't refer to a node in this list"),
+      Error::EmptyList => write!(f, "list is empty"),
+      Error::CapacityExceeded => write!(f, "list is full"),
+    }
+  }
+}
+
+impl core::error::Error for Error {}
@@ -1,2 +1,12 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod error;
 pub mod linked_list;
 pub mod lru;
+pub mod map;
+// Checks for your own DLL or LRU backends. Always built for the crate's
+// own tests, which run every backend through it too.
+#[cfg(any(feature = "testkit", test))]
+pub mod testkit;
@@ -1,50 +1,413 @@
 pub mod veclist;
 pub mod cellist;
-use std::marker::PhantomData;
+// Pointers are checked against a 64 bit list id.
+#[cfg(target_has_atomic = "64")]
+pub mod ptrlist;
+pub mod arraylist;
+use crate::error::Error;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
 
 pub trait DLL<T> {
   type Pointer;
 
+  // What get and get_mut hand out. A backend that can lend plain
+  // references uses &T and &mut T; one that keeps nodes behind a
+  // RefCell returns its borrow guards, so borrows are always tracked.
+  type Ref<'a>: Deref<Target = T> where Self: 'a, T: 'a;
+  type RefMut<'a>: DerefMut<Target = T> where Self: 'a, T: 'a;
+
+  // Each backend knows how to hand out one &mut per node soundly, so
+  // each brings its own mutable iterator.
+  type IterMut<'a>: Iterator<Item = Self::RefMut<'a>>
+    + DoubleEndedIterator
+    + ExactSizeIterator
+    + FusedIterator
+  where Self: 'a, T: 'a;
+
   // How many are in the list?
   fn new() -> Self;
   fn size(&self) -> usize;
 
-  fn peek_front(&self) -> Option<&T>;
-  fn peek_back(&self) -> Option<&T>;
+  // Lists that can't grow past a fixed number of nodes say how many
+  // here, so an LRU over one can check its capacity against it.
+  fn max_size() -> Option<usize> {
+    None
+  }
+
+  // Whether additional more nodes fit. By default that is checked
+  // against max_size; lists that grow but still have a ceiling, like
+  // SmallLinkedList, check their own.
+  fn has_room(&self, additional: usize) -> bool {
+    Self::max_size().is_none_or(|max| self.size() + additional <= max)
+  }
+
+  // Makes room for additional more nodes, for lists that can. The
+  // rest just allocate as they go.
+  fn reserve(&mut self, _additional: usize) {}
+
+  fn peek_front(&self) -> Option<Self::Ref<'_>>;
+  fn peek_back(&self) -> Option<Self::Ref<'_>>;
   fn pop_front(&mut self) -> Option<T>;
   fn pop_back(&mut self) -> Option<T>;
 
   fn head(&self) -> Option<Self::Pointer>;
   fn tail(&self) -> Option<Self::Pointer>;
 
-  fn get(&self, ptr: &Self::Pointer) -> Option<&T>;
-  fn get_mut(&mut self, ptr: &Self::Pointer) -> Option<&mut T>;
+  fn get(&self, ptr: &Self::Pointer) -> Option<Self::Ref<'_>>;
+  fn get_mut(&mut self, ptr: &Self::Pointer) -> Option<Self::RefMut<'_>>;
   fn replace_val(&mut self, ptr: &Self::Pointer, elem: T);
 
   fn push_back(&mut self, elem: T) -> Self::Pointer;
+  // Put a new node right next to an existing one. Returns None, and
+  // drops elem, if the pointer doesn't refer to a node anymore.
+  fn insert_before(&mut self, ptr: &Self::Pointer, elem: T) -> Option<Self::Pointer>;
+  fn insert_after(&mut self, ptr: &Self::Pointer, elem: T) -> Option<Self::Pointer>;
   fn push_front(&mut self, elem: T) -> Self::Pointer;
+  // Takes the node out wherever it is. Returns None if the pointer
+  // doesn't refer to a node anymore.
+  fn remove(&mut self, ptr: &Self::Pointer) -> Option<T>;
   fn move_back(&mut self, ptr: &mut Self::Pointer);
   fn move_front(&mut self, ptr: &mut Self::Pointer);
 
+  // Moving whole runs of nodes between lists. Each backend documents
+  // what these cost and whether pointers survive the move.
+  //
+  // append moves every node of other to the back of this list, and
+  // prepend to the front. split_off cuts the list in two just before
+  // ptr, and returns the part that starts at ptr. splice_after moves
+  // every node of other in right after ptr. A stale ptr leaves both
+  // lists alone. None of them move the nodes already in this list, so
+  // pointers into it keep working. split_off is O(moved nodes) on every
+  // backend, even one that can relink in O(1), since both halves need
+  // their sizes and the part cut off has to be walked to count it.
+  fn append(&mut self, other: &mut Self) where Self: Sized;
+  fn split_off(&mut self, ptr: &Self::Pointer) -> Self where Self: Sized;
+  fn splice_after(&mut self, ptr: &Self::Pointer, other: &mut Self) where Self: Sized;
+
+  // The default pops other from the back one node at a time, which is
+  // O(other) and gives every moved element a new pointer.
+  fn prepend(&mut self, other: &mut Self) where Self: Sized {
+    while let Some(elem) = other.pop_back() {
+      self.push_front(elem);
+    }
+  }
+
   // Traversers, so that iter can use it.
   fn next_node(&self, ptr: &Self::Pointer) -> Option<Self::Pointer>;
   fn prev_node(&self, ptr: &Self::Pointer) -> Option<Self::Pointer>;
 
-  fn iter(&self) -> DLLIterator<T, Self> {
+  // Versions of the above that say what went wrong. The plain ones
+  // treat a stale pointer as "nothing there", which can hide a bug, so
+  // these turn it into Error::StalePointer.
+  fn try_get(&self, ptr: &Self::Pointer) -> Result<Self::Ref<'_>, Error> {
+    self.get(ptr).ok_or(Error::StalePointer)
+  }
+
+  fn try_get_mut(&mut self, ptr: &Self::Pointer) -> Result<Self::RefMut<'_>, Error> {
+    self.get_mut(ptr).ok_or(Error::StalePointer)
+  }
+
+  // Drops elem on an error, like insert_before.
+  fn try_replace_val(&mut self, ptr: &Self::Pointer, elem: T) -> Result<(), Error> {
+    let mut val = self.try_get_mut(ptr)?;
+    *val = elem;
+    Ok(())
+  }
+
+  fn try_remove(&mut self, ptr: &Self::Pointer) -> Result<T, Error> {
+    self.remove(ptr).ok_or(Error::StalePointer)
+  }
+
+  fn try_move_back(&mut self, ptr: &mut Self::Pointer) -> Result<(), Error> {
+    self.try_get(ptr)?;
+    self.move_back(ptr);
+    Ok(())
+  }
+
+  fn try_move_front(&mut self, ptr: &mut Self::Pointer) -> Result<(), Error> {
+    self.try_get(ptr)?;
+    self.move_front(ptr);
+    Ok(())
+  }
+
+  // Ok(None) at the end of the list, Err for a stale pointer.
+  fn try_next_node(&self, ptr: &Self::Pointer) -> Result<Option<Self::Pointer>, Error> {
+    self.try_get(ptr)?;
+    Ok(self.next_node(ptr))
+  }
+
+  fn try_prev_node(&self, ptr: &Self::Pointer) -> Result<Option<Self::Pointer>, Error> {
+    self.try_get(ptr)?;
+    Ok(self.prev_node(ptr))
+  }
+
+  fn try_pop_front(&mut self) -> Result<T, Error> {
+    self.pop_front().ok_or(Error::EmptyList)
+  }
+
+  fn try_pop_back(&mut self) -> Result<T, Error> {
+    self.pop_back().ok_or(Error::EmptyList)
+  }
+
+  // The plain versions panic when the list is full. These check
+  // has_room first and give Error::CapacityExceeded, dropping elem.
+  fn try_push_back(&mut self, elem: T) -> Result<Self::Pointer, Error> {
+    if !self.has_room(1) {
+      return Err(Error::CapacityExceeded);
+    }
+    Ok(self.push_back(elem))
+  }
+
+  fn try_push_front(&mut self, elem: T) -> Result<Self::Pointer, Error> {
+    if !self.has_room(1) {
+      return Err(Error::CapacityExceeded);
+    }
+    Ok(self.push_front(elem))
+  }
+
+  // A stale pointer is an error before a full list is.
+  fn try_insert_before(&mut self, ptr: &Self::Pointer, elem: T) -> Result<Self::Pointer, Error> {
+    self.try_get(ptr)?;
+    if !self.has_room(1) {
+      return Err(Error::CapacityExceeded);
+    }
+    self.insert_before(ptr, elem).ok_or(Error::StalePointer)
+  }
+
+  fn try_insert_after(&mut self, ptr: &Self::Pointer, elem: T) -> Result<Self::Pointer, Error> {
+    self.try_get(ptr)?;
+    if !self.has_room(1) {
+      return Err(Error::CapacityExceeded);
+    }
+    self.insert_after(ptr, elem).ok_or(Error::StalePointer)
+  }
+
+  // Leaves both lists alone if other won't all fit.
+  fn try_append(&mut self, other: &mut Self) -> Result<(), Error> where Self: Sized {
+    if !self.has_room(other.size()) {
+      return Err(Error::CapacityExceeded);
+    }
+    self.append(other);
+    Ok(())
+  }
+
+  fn iter(&self) -> DLLIterator<'_, T, Self> {
     DLLIterator {
-      list: &self,
+      list: self,
       curr: self.head(),
+      back: self.tail(),
+      remaining: self.size(),
       wokka: PhantomData,
     }
   }
 
-  fn iter_mut(&mut self) -> DLLMutIterator<T, Self> {
-    DLLMutIterator {
-      curr: self.head(),
-      list: self,
-      _wokka: PhantomData,
+  fn iter_mut(&mut self) -> Self::IterMut<'_>;
+
+  // Walks the whole list checking that it is wired up right. Backends
+  // that can check themselves override this; the rest always pass.
+  // The walk goes from the head checking every link both ways, which
+  // also catches a circle in the next links the first time it comes
+  // back round, so it always ends.
+  fn check_invariants(&self) -> Result<(), InvariantError> {
+    Ok(())
+  }
+
+  fn cursor_front(&self) -> Cursor<'_, T, Self> {
+    Cursor { curr: self.head(), list: self, _wokka: PhantomData }
+  }
+
+  fn cursor_back(&self) -> Cursor<'_, T, Self> {
+    Cursor { curr: self.tail(), list: self, _wokka: PhantomData }
+  }
+
+  fn cursor_front_mut(&mut self) -> CursorMut<'_, T, Self> {
+    CursorMut { curr: self.head(), list: self, _wokka: PhantomData }
+  }
+
+  fn cursor_back_mut(&mut self) -> CursorMut<'_, T, Self> {
+    CursorMut { curr: self.tail(), list: self, _wokka: PhantomData }
+  }
+
+  // Reordering in place. The defaults collect every pointer, O(n) extra
+  // space, and then call move_back on each, so they are only as kind to
+  // pointers as move_back is: where it hands out a new pointer, as on
+  // ArrayLinkedList, every pointer held from before goes stale. The Vec
+  // and Cell lists relink their nodes directly instead, in O(1) extra
+  // space, and every pointer survives.
+  fn reverse(&mut self) where Self: Sized {
+    for mut ptr in pointers(self).into_iter().rev() {
+      self.move_back(&mut ptr);
+    }
+  }
+
+  // Stable: equal elements keep their order. The default sorts the
+  // pointers with std's stable merge sort, then moves each node to the
+  // back in sorted order; see reverse for what that does to pointers.
+  fn sort_by<F>(&mut self, mut cmp: F)
+  where F: FnMut(&T, &T) -> Ordering, Self: Sized
+  {
+    let mut ptrs = pointers(self);
+    ptrs.sort_by(|a, b| cmp(&self.get(a).unwrap(), &self.get(b).unwrap()));
+    for mut ptr in ptrs {
+      self.move_back(&mut ptr);
+    }
+  }
+
+  // Keeps only the elements pred says yes to. O(n) for every backend,
+  // since each remove is O(1).
+  fn retain<F>(&mut self, mut pred: F)
+  where F: FnMut(&T) -> bool, Self: Sized
+  {
+    let mut at = self.head();
+    while let Some(ptr) = at {
+      at = self.next_node(&ptr);
+      let keep = pred(&self.get(&ptr).unwrap());
+      if !keep {
+        self.remove(&ptr);
+      }
     }
   }
+
+  // Takes out and hands back the elements pred says yes to, as the
+  // iterator reaches them. Stopping early leaves the rest in the list.
+  fn drain_filter<F>(&mut self, pred: F) -> DrainFilter<'_, T, Self, F>
+  where F: FnMut(&mut T) -> bool, Self: Sized
+  {
+    DrainFilter { next: self.head(), list: self, pred, _wokka: PhantomData }
+  }
+}
+
+// Every pointer in the list, front to back.
+fn pointers<T, L: DLL<T>>(list: &L) -> Vec<L::Pointer> {
+  let mut ptrs = Vec::with_capacity(list.size());
+  let mut at = list.head();
+  while let Some(ptr) = at {
+    at = list.next_node(&ptr);
+    ptrs.push(ptr);
+  }
+  ptrs
+}
+
+pub struct DrainFilter<'a, T, L, F>
+where L: DLL<T>
+{
+  list: &'a mut L,
+  next: Option<L::Pointer>,
+  pred: F,
+  _wokka: PhantomData<T>,
+}
+
+impl<T, L, F> Iterator for DrainFilter<'_, T, L, F>
+where L: DLL<T>, F: FnMut(&mut T) -> bool
+{
+  type Item = T;
+  fn next(&mut self) -> Option<T> {
+    while let Some(ptr) = self.next.take() {
+      self.next = self.list.next_node(&ptr);
+      let take = (self.pred)(&mut *self.list.get_mut(&ptr)?);
+      if take {
+        return self.list.remove(&ptr);
+      }
+    }
+    None
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (0, Some(self.list.size()))
+  }
+}
+
+impl<T, L, F> FusedIterator for DrainFilter<'_, T, L, F>
+where L: DLL<T>, F: FnMut(&mut T) -> bool {}
+
+// What check_invariants found wrong. Positions count nodes from the
+// head, indexes are spine slots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantError {
+  // A link leads to a slot with no node in it, or a node that's gone.
+  DanglingLink { position: usize },
+  // The node at position doesn't point back at the one before it.
+  BrokenBackLink { position: usize },
+  // The tail isn't the last node reached from the head.
+  WrongTail,
+  SizeMismatch { size: usize, reachable: usize },
+  // Slots with a node in them that the walk from the head never
+  // reached.
+  Unreachable { count: usize },
+  FreeListMismatch { free: usize, empty: usize },
+  // A free list entry for a slot that's in use, out of range, or
+  // already on the free list.
+  BadFreeSlot { index: usize },
+  // The rest are KeyHolder's. Keys don't have to be Debug, so these
+  // can't say which key.
+  KeyCountMismatch { keys: usize, entries: usize },
+  // A key whose pointer is stale.
+  StaleKey,
+  // A key whose pointer leads to another key's entry.
+  WrongKey,
+  OverCapacity { size: usize, capacity: usize },
+}
+
+impl fmt::Display for InvariantError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      InvariantError::DanglingLink { position } =>
+        write!(f, "the link to node {position} leads nowhere"),
+      InvariantError::BrokenBackLink { position } =>
+        write!(f, "node {position} doesn't point back at the node before it"),
+      InvariantError::WrongTail => write!(f, "the tail isn't the last node"),
+      InvariantError::SizeMismatch { size, reachable } =>
+        write!(f, "size is {size}, but {reachable} nodes can be reached"),
+      InvariantError::Unreachable { count } =>
+        write!(f, "{count} nodes can't be reached from the head"),
+      InvariantError::FreeListMismatch { free, empty } =>
+        write!(f, "{free} slots are on the free list, but {empty} are empty"),
+      InvariantError::BadFreeSlot { index } =>
+        write!(f, "slot {index} is on the free list, but can't be used"),
+      InvariantError::KeyCountMismatch { keys, entries } =>
+        write!(f, "the map has {keys} keys, but the list has {entries} entries"),
+      InvariantError::StaleKey => write!(f, "a key's pointer doesn't lead to a node"),
+      InvariantError::WrongKey => write!(f, "a key's pointer leads to another key's entry"),
+      InvariantError::OverCapacity { size, capacity } =>
+        write!(f, "{size} entries is over the capacity of {capacity}"),
+    }
+  }
+}
+
+impl core::error::Error for InvariantError {}
+
+// In the crate's own debug test builds, lists and caches check
+// themselves after every change, so a broken link fails the test that
+// broke it instead of some later one. Checking is O(n), so past a few
+// thousand nodes it is skipped to keep the big tests from going
+// quadratic.
+#[cfg(all(test, debug_assertions))]
+pub(crate) fn debug_validate(size: usize, check: impl FnOnce() -> Result<(), InvariantError>) {
+  if size <= 4096 {
+    if let Err(err) = check() {
+      panic!("invariant broken: {err}");
+    }
+  }
+}
+
+#[cfg(not(all(test, debug_assertions)))]
+#[inline(always)]
+pub(crate) fn debug_validate(_: usize, _: impl FnOnce() -> Result<(), InvariantError>) {}
+
+// Ids for the index based lists, so each one can turn away pointers
+// another list handed out. A u32 wraps after four billion lists, but
+// all a reused id costs is that check: the index and generation still
+// keep every lookup in bounds.
+static NEXT_LIST_ID: AtomicU32 = AtomicU32::new(0);
+
+pub(crate) fn next_list_id() -> u32 {
+  NEXT_LIST_ID.fetch_add(1, AtomicOrdering::Relaxed)
 }
 
 pub struct DLLIntoIter<T, L: DLL<T>> {
@@ -57,35 +420,128 @@ impl<T, L: DLL<T>> Iterator for DLLIntoIter<T, L> {
   fn next(&mut self) -> Option<Self::Item> {
     self.list.pop_front()
   }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.list.size(), Some(self.list.size()))
+  }
+}
+
+impl<T, L: DLL<T>> DoubleEndedIterator for DLLIntoIter<T, L> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.list.pop_back()
+  }
 }
 
+impl<T, L: DLL<T>> ExactSizeIterator for DLLIntoIter<T, L> {}
+impl<T, L: DLL<T>> FusedIterator for DLLIntoIter<T, L> {}
+
+// Walks in from both ends at once. Counting down from the list's size
+// is what tells it the two ends have met, so it never hands out the same
+// node twice.
 pub struct DLLIterator<'a, T, L>
 where T: 'a, L: DLL<T> + ?Sized
 {
   list: &'a L,
   curr: Option<L::Pointer>,
+  back: Option<L::Pointer>,
+  remaining: usize,
   wokka: PhantomData<T>,
 }
 
 impl<'a, T, L> Iterator for DLLIterator<'a, T, L>
-where L: DLL<T>
+where L: DLL<T> + ?Sized
 {
-  type Item = &'a T;
+  type Item = L::Ref<'a>;
   fn next(&mut self) -> Option<Self::Item> {
-    let curr_ptr = self.curr.as_ref();
+    if self.remaining == 0 {
+      return None;
+    }
+
+    let curr_ptr = self.curr.take()?;
+    self.curr = self.list.next_node(&curr_ptr);
+    self.remaining -= 1;
+    self.list.get(&curr_ptr)
+  }
 
-    if let None = curr_ptr {
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a, T, L> DoubleEndedIterator for DLLIterator<'a, T, L>
+where L: DLL<T> + ?Sized
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
       return None;
     }
 
-    let next_node = self.list.next_node(curr_ptr.unwrap());
-    let item = self.list.get(curr_ptr.as_ref().clone().unwrap());
-    self.curr = next_node;
-    item
+    let back_ptr = self.back.take()?;
+    self.back = self.list.prev_node(&back_ptr);
+    self.remaining -= 1;
+    self.list.get(&back_ptr)
+  }
+}
+
+impl<'a, T, L> ExactSizeIterator for DLLIterator<'a, T, L> where L: DLL<T> + ?Sized {}
+impl<'a, T, L> FusedIterator for DLLIterator<'a, T, L> where L: DLL<T> + ?Sized {}
+
+// A position in a list, like std's LinkedList cursors. The cursor sits
+// either on a node or on the "ghost" position past the tail and before
+// the head, so moving off either end and back wraps around the list.
+pub struct Cursor<'a, T, L>
+where T: 'a, L: DLL<T> + ?Sized
+{
+  list: &'a L,
+  curr: Option<L::Pointer>,
+  _wokka: PhantomData<&'a T>,
+}
+
+impl<'a, T, L> Cursor<'a, T, L>
+where L: DLL<T> + ?Sized
+{
+  // The pointer to the current node, None on the ghost.
+  pub fn pointer(&self) -> Option<&L::Pointer> {
+    self.curr.as_ref()
+  }
+
+  pub fn current(&self) -> Option<L::Ref<'a>> {
+    self.list.get(self.curr.as_ref()?)
+  }
+
+  pub fn move_next(&mut self) {
+    self.curr = match &self.curr {
+      Some(ptr) => self.list.next_node(ptr),
+      None => self.list.head(),
+    };
+  }
+
+  pub fn move_prev(&mut self) {
+    self.curr = match &self.curr {
+      Some(ptr) => self.list.prev_node(ptr),
+      None => self.list.tail(),
+    };
+  }
+
+  pub fn peek_next(&self) -> Option<L::Ref<'a>> {
+    let next = match &self.curr {
+      Some(ptr) => self.list.next_node(ptr),
+      None => self.list.head(),
+    };
+    self.list.get(&next?)
+  }
+
+  pub fn peek_prev(&self) -> Option<L::Ref<'a>> {
+    let prev = match &self.curr {
+      Some(ptr) => self.list.prev_node(ptr),
+      None => self.list.tail(),
+    };
+    self.list.get(&prev?)
   }
 }
 
-pub struct DLLMutIterator<'a, T, L>
+// A cursor that can also change the list around it.
+pub struct CursorMut<'a, T, L>
 where T: 'a, L: DLL<T> + ?Sized
 {
   list: &'a mut L,
@@ -93,30 +549,102 @@ where T: 'a, L: DLL<T> + ?Sized
   _wokka: PhantomData<&'a T>,
 }
 
-impl<'a, T, L> Iterator for DLLMutIterator<'a, T, L>
-where L: DLL<T>
+impl<'a, T, L> CursorMut<'a, T, L>
+where L: DLL<T> + ?Sized
 {
-  type Item = &'a mut T;
-  fn next(&mut self) -> Option<Self::Item> {
-    let curr_ptr = self.curr.as_ref();
+  pub fn pointer(&self) -> Option<&L::Pointer> {
+    self.curr.as_ref()
+  }
 
-    if let None = &curr_ptr {
-      return None;
+  // A read-only cursor at the same position, borrowing this one.
+  pub fn as_cursor(&self) -> Cursor<'_, T, L>
+  where L::Pointer: Clone
+  {
+    Cursor { list: self.list, curr: self.curr.clone(), _wokka: PhantomData }
+  }
+
+  pub fn current(&mut self) -> Option<L::RefMut<'_>> {
+    self.list.get_mut(self.curr.as_ref()?)
+  }
+
+  pub fn move_next(&mut self) {
+    self.curr = match &self.curr {
+      Some(ptr) => self.list.next_node(ptr),
+      None => self.list.head(),
+    };
+  }
+
+  pub fn move_prev(&mut self) {
+    self.curr = match &self.curr {
+      Some(ptr) => self.list.prev_node(ptr),
+      None => self.list.tail(),
+    };
+  }
+
+  pub fn peek_next(&mut self) -> Option<L::RefMut<'_>> {
+    let next = match &self.curr {
+      Some(ptr) => self.list.next_node(ptr),
+      None => self.list.head(),
+    };
+    self.list.get_mut(&next?)
+  }
+
+  pub fn peek_prev(&mut self) -> Option<L::RefMut<'_>> {
+    let prev = match &self.curr {
+      Some(ptr) => self.list.prev_node(ptr),
+      None => self.list.tail(),
+    };
+    self.list.get_mut(&prev?)
+  }
+
+  // Takes the current node out and moves on to the one after it. Does
+  // nothing on the ghost.
+  pub fn remove_current(&mut self) -> Option<T> {
+    let curr = self.curr.take()?;
+    self.curr = self.list.next_node(&curr);
+    self.list.remove(&curr)
+  }
+
+  // On the ghost, inserting before means pushing to the back.
+  pub fn insert_before(&mut self, elem: T) -> Option<L::Pointer> {
+    match &self.curr {
+      Some(ptr) => self.list.insert_before(ptr, elem),
+      None => Some(self.list.push_back(elem)),
     }
+  }
 
-    let next_node = self.list.next_node(curr_ptr.unwrap());
-
-    // The problem is the mutable reference is moved to this
-    // function once we grab it, and we can't return it here
-    // because we want to be able to call next again. Rust
-    // is deadly afraid of you returning the same &mut twice.
-    let output = self.list.get_mut(&curr_ptr.unwrap());
-    self.curr = next_node;
-    
-    unsafe {
-      // But since I know they are different every time, let's ignore it
-      // and de-reference.
-      output.map(|n| &mut *(n as *mut T))
+  // On the ghost, inserting after means pushing to the front.
+  pub fn insert_after(&mut self, elem: T) -> Option<L::Pointer> {
+    match &self.curr {
+      Some(ptr) => self.list.insert_after(ptr, elem),
+      None => Some(self.list.push_front(elem)),
+    }
+  }
+
+  // Moves every node of other in after the current one, keeping their
+  // order. On the ghost, they go to the front of the list.
+  pub fn splice_after(&mut self, mut other: L)
+  where L: Sized
+  {
+    match &self.curr {
+      Some(ptr) => self.list.splice_after(ptr, &mut other),
+      None => self.list.prepend(&mut other),
+    }
+  }
+
+  // Moves every node of other in before the current one, keeping their
+  // order. On the ghost, they go to the back of the list.
+  pub fn splice_before(&mut self, mut other: L)
+  where L: Sized
+  {
+    let prev = match &self.curr {
+      Some(ptr) => self.list.prev_node(ptr),
+      None => return self.list.append(&mut other),
+    };
+
+    match prev {
+      Some(prev) => self.list.splice_after(&prev, &mut other),
+      None => self.list.prepend(&mut other),
     }
   }
 }
@@ -126,6 +654,8 @@ mod macros {
   macro_rules! dll_tests {
     ($type:ident) => {
       #[cfg(test)]
+      // The tests are kept as they were written, lint or not.
+      #[allow(clippy::assign_op_pattern)]
       mod test {
         use super::*;
 
@@ -142,7 +672,10 @@ mod macros {
 
           let iter = l.iter_mut();
 
-          for i in iter {
+          // The Cell list hands out guards that need a mut binding,
+          // plain &mut's from the Vec list don't.
+          #[allow(unused_mut)]
+          for mut i in iter {
             *i = 100 + *i;
           }
 
@@ -151,6 +684,265 @@ mod macros {
           }
         }
 
+        #[test]
+        fn try_test() {
+          use crate::error::Error;
+
+          let mut l: $type<i32> = $type::new();
+          assert_eq!(l.try_pop_front(), Err(Error::EmptyList));
+          assert_eq!(l.try_pop_back(), Err(Error::EmptyList));
+
+          let mut one = l.push_back(1);
+          let two = l.push_back(2);
+          assert_eq!(l.try_get(&one).as_deref(), Ok(&1));
+          assert!(l.try_next_node(&one).unwrap().is_some());
+          assert!(l.try_next_node(&two).unwrap().is_none());
+          assert!(l.try_prev_node(&one).unwrap().is_none());
+          assert_eq!(l.try_replace_val(&two, 20), Ok(()));
+          assert_eq!(l.try_move_back(&mut one), Ok(()));
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![20, 1]);
+
+          // A removed node's pointer gives errors instead of panics
+          assert_eq!(l.try_remove(&two), Ok(20));
+          let mut stale = two;
+          assert_eq!(l.try_remove(&stale), Err(Error::StalePointer));
+          assert!(matches!(l.try_get(&stale), Err(Error::StalePointer)));
+          assert!(matches!(l.try_get_mut(&stale), Err(Error::StalePointer)));
+          assert_eq!(l.try_replace_val(&stale, 5), Err(Error::StalePointer));
+          assert_eq!(l.try_move_back(&mut stale), Err(Error::StalePointer));
+          assert_eq!(l.try_move_front(&mut stale), Err(Error::StalePointer));
+          assert_eq!(l.try_next_node(&stale).err(), Some(Error::StalePointer));
+          assert_eq!(l.try_prev_node(&stale).err(), Some(Error::StalePointer));
+
+          // The plain versions quietly do nothing
+          l.replace_val(&stale, 5);
+          l.move_back(&mut stale);
+          l.move_front(&mut stale);
+          assert!(l.next_node(&stale).is_none());
+          assert!(l.prev_node(&stale).is_none());
+          assert_eq!(l.try_pop_back(), Ok(1));
+          assert_eq!(l.size(), 0);
+
+          // Adding with room to spare works like the plain versions.
+          let mid = l.try_push_back(2).unwrap();
+          l.try_push_front(0).unwrap();
+          l.try_insert_before(&mid, 1).unwrap();
+          l.try_insert_after(&mid, 3).unwrap();
+          assert!(matches!(l.try_insert_after(&stale, 9), Err(Error::StalePointer)));
+          assert!(matches!(l.try_insert_before(&stale, 9), Err(Error::StalePointer)));
+          let mut other: $type<i32> = $type::new();
+          other.push_back(4);
+          assert_eq!(l.try_append(&mut other), Ok(()));
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+          assert!(l.has_room(1));
+        }
+
+        #[test]
+        fn reorder_test() {
+          let mut l: $type<(i32, char)> = $type::new();
+          l.reverse();
+          l.sort_by(|a, b| a.cmp(b));
+          assert_eq!(l.size(), 0);
+
+          for (i, c) in "sortable".chars().enumerate() {
+            l.push_back((i as i32 % 3, c));
+          }
+          l.reverse();
+          let chars: String = l.iter().map(|n| n.1).collect();
+          assert_eq!(chars, "elbatros");
+
+          // Equal keys keep the order they had
+          l.sort_by(|a, b| a.0.cmp(&b.0));
+          let sorted: Vec<_> = l.iter().map(|n| *n).collect();
+          assert_eq!(sorted, vec![
+            (0, 'l'), (0, 't'), (0, 's'),
+            (1, 'e'), (1, 'a'), (1, 'o'),
+            (2, 'b'), (2, 'r'),
+          ]);
+          let back: Vec<_> = l.iter().rev().map(|n| n.1).collect();
+          assert_eq!(back, vec!['r', 'b', 'o', 'a', 'e', 's', 't', 'l']);
+
+          l.retain(|n| n.1 != 'r' && n.1 != 'a');
+          let chars: String = l.iter().map(|n| n.1).collect();
+          assert_eq!(chars, "ltseob");
+
+          // drain_filter can change what it looks at, and stopping early
+          // leaves the rest alone
+          {
+            let mut vowels = l.drain_filter(|n| {
+              n.0 += 10;
+              "aeiou".contains(n.1)
+            });
+            assert_eq!(vowels.next(), Some((11, 'e')));
+          }
+          let left: Vec<_> = l.iter().map(|n| *n).collect();
+          assert_eq!(left, vec![(10, 'l'), (10, 't'), (10, 's'), (1, 'o'), (2, 'b')]);
+          let rest: Vec<_> = l.drain_filter(|n| {
+            n.0 += 10;
+            n.0 >= 20
+          }).collect();
+          assert_eq!(rest, vec![(20, 'l'), (20, 't'), (20, 's')]);
+          assert_eq!(l.size(), 2);
+          assert_eq!(l.peek_back().as_deref(), Some(&(12, 'b')));
+        }
+
+        #[test]
+        fn std_traits_test() {
+          use core::hash::{Hash, Hasher};
+          use std::collections::hash_map::DefaultHasher;
+
+          fn hash_of<H: Hash>(h: &H) -> u64 {
+            let mut state = DefaultHasher::new();
+            h.hash(&mut state);
+            state.finish()
+          }
+
+          let mut l: $type<i32> = (1..4).collect();
+          assert_eq!(format!("{l:?}"), "[1, 2, 3]");
+          l.extend([4, 5]);
+          assert_eq!(l.size(), 5);
+
+          // Equal means the same elements in the same order, however
+          // the list got there.
+          let mut other: $type<i32> = Default::default();
+          assert_eq!(format!("{other:?}"), "[]");
+          for i in (1..6).rev() {
+            other.push_front(i);
+          }
+          other.push_front(0);
+          other.pop_front();
+          assert_eq!(l, other);
+          assert_eq!(hash_of(&l), hash_of(&other));
+
+          other.pop_back();
+          assert_ne!(l, other);
+          other.push_back(6);
+          assert_ne!(l, other);
+          assert_ne!(hash_of(&l), hash_of(&other));
+        }
+
+        #[test]
+        fn splice_at_head_keeps_pointers() {
+          let mut l: $type<i32> = $type::new();
+          let one = l.push_back(1);
+          let two = l.push_back(2);
+
+          // Splicing in at the head moves the new nodes, never the ones
+          // already there, so their pointers still read their own
+          // elements.
+          let mut other: $type<i32> = $type::new();
+          other.push_back(0);
+          l.cursor_front_mut().splice_before(other);
+          let mut other: $type<i32> = $type::new();
+          other.push_back(-1);
+          let mut c = l.cursor_front_mut();
+          c.move_prev();
+          c.splice_after(other);
+          let mut other: $type<i32> = $type::new();
+          other.push_back(-3);
+          other.push_back(-2);
+          l.prepend(&mut other);
+          assert_eq!(other.size(), 0);
+
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![-3, -2, -1, 0, 1, 2]);
+          assert_eq!(l.size(), 6);
+          assert_eq!(l.get(&one).as_deref(), Some(&1));
+          assert_eq!(l.get(&two).as_deref(), Some(&2));
+          assert_eq!(l.peek_back().as_deref(), Some(&2));
+        }
+
+        #[test]
+        fn foreign_pointers() {
+          use crate::error::Error;
+
+          // Two lists laid out the same way, so a pointer from one lines
+          // up with a node in the other.
+          let mut a: $type<i32> = $type::new();
+          let mut b: $type<i32> = $type::new();
+          let ptr = a.push_back(1);
+          a.push_back(2);
+          b.push_back(10);
+          b.push_back(20);
+
+          assert!(b.get(&ptr).is_none());
+          assert!(b.get_mut(&ptr).is_none());
+          assert!(b.next_node(&ptr).is_none());
+          assert!(b.prev_node(&ptr).is_none());
+          assert_eq!(b.remove(&ptr), None);
+          assert_eq!(b.try_remove(&ptr), Err(Error::StalePointer));
+          assert!(b.insert_before(&ptr, 0).is_none());
+          assert!(b.insert_after(&ptr, 0).is_none());
+          assert!(matches!(b.try_insert_before(&ptr, 0), Err(Error::StalePointer)));
+          assert!(matches!(b.try_insert_after(&ptr, 0), Err(Error::StalePointer)));
+          b.replace_val(&ptr, 0);
+          let mut moved = a.head().unwrap();
+          assert_eq!(b.try_move_front(&mut moved), Err(Error::StalePointer));
+          assert_eq!(b.split_off(&ptr).size(), 0);
+          let mut other: $type<i32> = $type::new();
+          other.push_back(30);
+          b.splice_after(&ptr, &mut other);
+          assert_eq!(other.size(), 1);
+
+          // Neither list was touched.
+          assert_eq!(a.iter().map(|n| *n).collect::<Vec<_>>(), vec![1, 2]);
+          assert_eq!(b.iter().map(|n| *n).collect::<Vec<_>>(), vec![10, 20]);
+          assert_eq!(a.get(&ptr).as_deref(), Some(&1));
+          assert_eq!(a.check_invariants(), Ok(()));
+          assert_eq!(b.check_invariants(), Ok(()));
+        }
+
+        #[test]
+        fn double_ended_test() {
+          let mut l: $type<i32> = $type::new();
+          assert_eq!(l.iter().next_back().as_deref(), None);
+          for i in 1..=5 {
+            l.push_back(i);
+          }
+
+          assert_eq!(l.iter().len(), 5);
+          assert_eq!(l.iter().rev().map(|n| *n).collect::<Vec<_>>(), vec![5, 4, 3, 2, 1]);
+          assert_eq!(l.iter().rposition(|n| *n == 2), Some(1));
+
+          // Both ends meet in the middle without overlapping
+          let mut iter = l.iter();
+          assert_eq!(iter.next().as_deref(), Some(&1));
+          assert_eq!(iter.next_back().as_deref(), Some(&5));
+          assert_eq!(iter.len(), 3);
+          assert_eq!(iter.next_back().as_deref(), Some(&4));
+          assert_eq!(iter.next().as_deref(), Some(&2));
+          assert_eq!(iter.next().as_deref(), Some(&3));
+          assert_eq!(iter.len(), 0);
+          assert_eq!(iter.next_back().as_deref(), None);
+          assert_eq!(iter.next().as_deref(), None);
+
+          let mut iter = l.iter_mut();
+          assert_eq!(iter.len(), 5);
+          *iter.next_back().unwrap() *= 10;
+          *iter.next().unwrap() *= 10;
+          assert_eq!(iter.len(), 3);
+          #[allow(unused_mut)]
+          for mut n in iter.rev() {
+            *n += 1;
+          }
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![10, 3, 4, 5, 50]);
+
+          // Everything iter_mut hands out can be held at once
+          let mut all: Vec<_> = l.iter_mut().collect();
+          let (first, rest) = all.split_first_mut().unwrap();
+          for n in rest.iter_mut() {
+            **n += **first;
+          }
+          **first = 10;
+          drop(all);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![10, 13, 14, 15, 60]);
+
+          let mut iter = l.into_iter();
+          assert_eq!(iter.len(), 5);
+          assert_eq!(iter.next_back(), Some(60));
+          assert_eq!(iter.next(), Some(10));
+          assert_eq!(iter.rev().collect::<Vec<_>>(), vec![15, 14, 13]);
+        }
+
         #[test]
         fn test() {
           let mut l: $type<i32> = $type::new();
@@ -162,33 +954,33 @@ mod macros {
           let third = l.push_back(20);
           assert_eq!(l.size(), 3);
           // Can be got, with a pointer
-          assert_eq!(l.get(&first), Some(&100));
-          assert_eq!(l.get(&second), Some(&-1));
-          assert_eq!(l.get(&third), Some(&20));
+          assert_eq!(l.get(&first).as_deref(), Some(&100));
+          assert_eq!(l.get(&second).as_deref(), Some(&-1));
+          assert_eq!(l.get(&third).as_deref(), Some(&20));
         
           //Can remove
-          assert_eq!(l.peek_front(), Some(&100));
+          assert_eq!(l.peek_front().as_deref(), Some(&100));
           assert_eq!(l.pop_front(), Some(100));
         
           assert_eq!(l.size(), 2);
         
-          assert_eq!(l.peek_front(), Some(&-1));
+          assert_eq!(l.peek_front().as_deref(), Some(&-1));
           assert_eq!(l.pop_front(), Some(-1));
         
           assert_eq!(l.size(), 1);
         
-          assert_eq!(l.peek_front(), Some(&20));
+          assert_eq!(l.peek_front().as_deref(), Some(&20));
           assert_eq!(l.pop_front(), Some(20));
         
           assert_eq!(l.size(), 0);
         
-          assert_eq!(l.peek_front(), None);
+          assert_eq!(l.peek_front().as_deref(), None);
           assert_eq!(l.pop_front(), None);
         
           assert_eq!(l.size(), 0);
         
           l.push_back(10);
-          assert_eq!(l.peek_front(), Some(&10));
+          assert_eq!(l.peek_front().as_deref(), Some(&10));
           assert_eq!(l.pop_front(), Some(10));
           assert_eq!(l.pop_front(), None);
         
@@ -198,8 +990,8 @@ mod macros {
           l.push_back(2);
           l.move_back(&mut ptr);
 
-          assert_eq!(l.get(&l.head().unwrap()), l.peek_front());
-          assert_eq!(l.get(&l.tail().unwrap()), l.peek_back());
+          assert_eq!(l.get(&l.head().unwrap()).as_deref(), l.peek_front().as_deref());
+          assert_eq!(l.get(&l.tail().unwrap()).as_deref(), l.peek_back().as_deref());
         
           assert_eq!(l.pop_front(), Some(1));
           assert_eq!(l.pop_back(), Some(3));
@@ -207,11 +999,11 @@ mod macros {
         
           // Can replace value at a pointer.
           let ptr = l.push_back(10);
-          assert_eq!(l.peek_front(), Some(&10));
-          assert_eq!(l.peek_back(), Some(&10));
+          assert_eq!(l.peek_front().as_deref(), Some(&10));
+          assert_eq!(l.peek_back().as_deref(), Some(&10));
           l.replace_val(&ptr, 40);
-          assert_eq!(l.peek_front(), Some(&40));
-          assert_eq!(l.peek_back(), Some(&40));
+          assert_eq!(l.peek_front().as_deref(), Some(&40));
+          assert_eq!(l.peek_back().as_deref(), Some(&40));
           l.replace_val(&ptr, 100);
           assert_eq!(l.pop_front(), Some(100));
           assert_eq!(l.pop_back(), None);
@@ -225,21 +1017,21 @@ mod macros {
           let ptr2_again = l.prev_node(&ptr3).unwrap();
           let ptr1_again = l.prev_node(&ptr2_again).unwrap();
         
-          assert_eq!(l.get(&ptr2), Some(&200));
-          assert_eq!(l.get(&ptr3), Some(&300));
+          assert_eq!(l.get(&ptr2).as_deref(), Some(&200));
+          assert_eq!(l.get(&ptr3).as_deref(), Some(&300));
           assert_eq!(l.next_node(&ptr3).is_none(), true);
           l.push_back(400);
           let mut ptr4 = l.next_node(&ptr3).unwrap();
-          assert_eq!(l.get(&ptr4), Some(&400));
-          assert_eq!(l.get(&ptr2_again), Some(&200));
-          assert_eq!(l.get(&ptr1_again), Some(&100));
+          assert_eq!(l.get(&ptr4).as_deref(), Some(&400));
+          assert_eq!(l.get(&ptr2_again).as_deref(), Some(&200));
+          assert_eq!(l.get(&ptr1_again).as_deref(), Some(&100));
           assert_eq!(l.prev_node(&ptr1_again).is_none(), true);
         
           l.move_back(&mut ptr1);
           l.move_front(&mut ptr4);
         
-          assert_eq!(l.get(&l.head().unwrap()), Some(&400));
-          assert_eq!(l.get(&l.tail().unwrap()), Some(&100));
+          assert_eq!(l.get(&l.head().unwrap()).as_deref(), Some(&400));
+          assert_eq!(l.get(&l.tail().unwrap()).as_deref(), Some(&100));
           l.move_front(&mut ptr1);
           l.move_back(&mut ptr4);
 
@@ -247,6 +1039,180 @@ mod macros {
           for (i, n) in l.iter().enumerate() {
             assert_eq!(*n, (i as i32 + 1) * 100);
           }
+
+          // Can remove from anywhere
+          let mut l: $type<i32> = $type::new();
+          let first = l.push_back(1);
+          let middle = l.push_back(2);
+          let last = l.push_back(3);
+          assert_eq!(l.remove(&middle), Some(2));
+          assert_eq!(l.remove(&middle), None);
+          assert_eq!(l.size(), 2);
+          assert_eq!(l.next_node(&first).and_then(|p| l.get(&p).as_deref().copied()), Some(3));
+          assert_eq!(l.prev_node(&last).and_then(|p| l.get(&p).as_deref().copied()), Some(1));
+          assert_eq!(l.remove(&last), Some(3));
+          assert_eq!(l.peek_back().as_deref(), Some(&1));
+          assert_eq!(l.remove(&first), Some(1));
+          assert_eq!(l.size(), 0);
+          assert_eq!(l.peek_front().as_deref(), None);
+          assert_eq!(l.peek_back().as_deref(), None);
+          l.push_back(4);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![4]);
+
+          // Can insert next to any node
+          let mut l: $type<i32> = $type::new();
+          let two = l.push_back(2);
+          let one = l.insert_before(&two, 1).unwrap();
+          let four = l.insert_after(&two, 4).unwrap();
+          l.insert_after(&two, 3).unwrap();
+          l.insert_before(&one, 0).unwrap();
+          l.insert_after(&four, 5).unwrap();
+          assert_eq!(l.size(), 6);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5]);
+          assert_eq!(l.peek_front().as_deref(), Some(&0));
+          assert_eq!(l.peek_back().as_deref(), Some(&5));
+          let mut back = Vec::new();
+          let mut ptr = l.tail();
+          while let Some(p) = ptr {
+            back.push(*l.get(&p).unwrap());
+            ptr = l.prev_node(&p);
+          }
+          assert_eq!(back, vec![5, 4, 3, 2, 1, 0]);
+          l.remove(&two);
+          assert!(l.insert_after(&two, 100).is_none());
+          assert!(l.insert_before(&two, 100).is_none());
+          assert_eq!(l.size(), 5);
+
+          // Cursors walk both ways and wrap through the ghost
+          let mut l: $type<i32> = $type::new();
+          for i in 1..=3 {
+            l.push_back(i);
+          }
+          let mut c = l.cursor_front();
+          assert_eq!(c.current().as_deref(), Some(&1));
+          assert_eq!(c.peek_prev().as_deref(), None);
+          assert_eq!(c.peek_next().as_deref(), Some(&2));
+          c.move_next();
+          c.move_next();
+          assert_eq!(c.current().as_deref(), Some(&3));
+          c.move_next();
+          assert_eq!(c.current().as_deref(), None);
+          assert!(c.pointer().is_none());
+          assert_eq!(c.peek_next().as_deref(), Some(&1));
+          assert_eq!(c.peek_prev().as_deref(), Some(&3));
+          c.move_next();
+          assert_eq!(c.current().as_deref(), Some(&1));
+          c.move_prev();
+          c.move_prev();
+          assert_eq!(c.current().as_deref(), Some(&3));
+          assert_eq!(l.cursor_back().current().as_deref(), Some(&3));
+
+          // and can edit in place
+          let mut c = l.cursor_front_mut();
+          *c.current().unwrap() = 10;
+          c.move_next();
+          assert_eq!(c.remove_current(), Some(2));
+          assert_eq!(c.current().as_deref(), Some(&3));
+          c.insert_before(20);
+          c.insert_after(40);
+          *c.peek_next().unwrap() += 1;
+          *c.peek_prev().unwrap() += 1;
+          assert_eq!(c.as_cursor().current().as_deref(), Some(&3));
+          c.move_next();
+          c.move_next();
+          assert_eq!(c.current().as_deref(), None);
+          assert_eq!(c.remove_current(), None);
+          c.insert_after(0);
+          c.insert_before(50);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![0, 10, 21, 3, 41, 50]);
+
+          // and can splice whole lists in
+          let mut other: $type<i32> = $type::new();
+          other.push_back(1);
+          other.push_back(2);
+          let mut c = l.cursor_front_mut();
+          c.move_next();
+          c.splice_after(other);
+          let mut other: $type<i32> = $type::new();
+          other.push_back(5);
+          other.push_back(6);
+          c.splice_before(other);
+          let mut c = l.cursor_back_mut();
+          c.move_next();
+          let mut other: $type<i32> = $type::new();
+          other.push_back(-2);
+          other.push_back(-1);
+          c.splice_after(other);
+          let mut other: $type<i32> = $type::new();
+          other.push_back(100);
+          c.splice_before(other);
+          c.move_prev();
+          let mut other: $type<i32> = $type::new();
+          other.push_back(101);
+          c.splice_after(other);
+          assert_eq!(
+            l.iter().map(|n| *n).collect::<Vec<_>>(),
+            vec![-2, -1, 0, 5, 6, 10, 1, 2, 21, 3, 41, 50, 100, 101]
+          );
+          assert_eq!(l.size(), 14);
+
+          // Can move whole runs of nodes between lists
+          let mut l: $type<i32> = $type::new();
+          let mut other: $type<i32> = $type::new();
+          l.append(&mut other);
+          assert_eq!(l.size(), 0);
+          other.push_back(1);
+          other.push_back(2);
+          l.append(&mut other);
+          assert_eq!(other.size(), 0);
+          assert_eq!(other.peek_front().as_deref(), None);
+          let mut other: $type<i32> = (5..=6).fold($type::new(), |mut o, i| { o.push_back(i); o });
+          l.append(&mut other);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![1, 2, 5, 6]);
+          assert_eq!(l.size(), 4);
+
+          let two = l.next_node(&l.head().unwrap()).unwrap();
+          let mut other: $type<i32> = (3..=4).fold($type::new(), |mut o, i| { o.push_back(i); o });
+          l.splice_after(&two, &mut other);
+          assert_eq!(other.size(), 0);
+          let six = l.tail().unwrap();
+          let mut other: $type<i32> = (7..=8).fold($type::new(), |mut o, i| { o.push_back(i); o });
+          l.splice_after(&six, &mut other);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+          assert_eq!(l.size(), 8);
+          assert_eq!(l.peek_back().as_deref(), Some(&8));
+
+          let mut five = l.head().unwrap();
+          for _ in 0..4 {
+            five = l.next_node(&five).unwrap();
+          }
+          let mut rest = l.split_off(&five);
+          assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+          assert_eq!(rest.iter().map(|n| *n).collect::<Vec<_>>(), vec![5, 6, 7, 8]);
+          assert_eq!((l.size(), rest.size()), (4, 4));
+          assert_eq!(l.peek_back().as_deref(), Some(&4));
+          assert_eq!(rest.peek_front().as_deref(), Some(&5));
+          assert_eq!(rest.pop_back(), Some(8));
+          assert_eq!(l.pop_back(), Some(4));
+
+          let first = l.head().unwrap();
+          let all = l.split_off(&first);
+          assert_eq!(l.size(), 0);
+          assert_eq!(l.peek_front().as_deref(), None);
+          assert_eq!(all.iter().map(|n| *n).collect::<Vec<_>>(), vec![1, 2, 3]);
+          assert_eq!(all.size(), 3);
+
+          // A pointer to a removed node doesn't reach whatever node is
+          // added after it
+          let mut l: $type<i32> = $type::new();
+          let stale = l.push_back(1);
+          l.push_back(2);
+          assert_eq!(l.pop_front(), Some(1));
+          let fresh = l.push_back(3);
+          assert_eq!(l.get(&stale).as_deref(), None);
+          l.replace_val(&stale, 100);
+          assert_eq!(l.get(&fresh).as_deref(), Some(&3));
+          assert_eq!(l.peek_front().as_deref(), Some(&2));
         }
       }
     }
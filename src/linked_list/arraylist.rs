@@ -0,0 +1,761 @@
+use crate::error::Error;
+use crate::linked_list::{DLL, next_list_id};
+use crate::map::HashMap;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+
+// Marks the ends of the list, where VectorLinkedList would use NONE.
+const NONE: usize = usize::MAX;
+
+struct BodyNode<T> {
+  elem: T,
+  next: usize,
+  prev: usize,
+}
+
+// Same as VectorLinkedList's slots: the generation is bumped whenever
+// the node in it is removed, so old pointers to it stop working.
+struct Slot<T> {
+  generation: u32,
+  node: Option<BodyNode<T>>,
+}
+
+// A VectorLinkedList that lives in a fixed array instead of a Vec. It
+// never allocates: the spine and the free list are both sized by N up
+// front, and pushing onto a full list is an error instead of a reason
+// to grow.
+//
+// The plain DLL methods that add nodes can't return an error, so they
+// panic when the list is full. DLL's try_ versions give
+// Error::CapacityExceeded instead, and the _or_return ones here hand
+// the element back.
+pub struct ArrayLinkedList<T, const N: usize> {
+  spine: [Slot<T>; N],
+  size: usize,
+  head: usize,
+  tail: usize,
+  // A stack of the free slots. Only the first free_len are meaningful.
+  free_list: [usize; N],
+  free_len: usize,
+  // Pointers carry it, like VectorLinkedList's, so one from another
+  // list is turned away.
+  id: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodePointer {
+  index: usize,
+  generation: u32,
+  list: u32,
+}
+
+// What an _or_return method hands back when the list has no room left: the
+// element that didn't fit.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CapacityError<T>(pub T);
+
+impl<T> CapacityError<T> {
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "list is full")
+  }
+}
+
+impl<T: fmt::Debug> core::error::Error for CapacityError<T> {}
+
+// For when the element itself isn't wanted back.
+impl<T> From<CapacityError<T>> for Error {
+  fn from(_: CapacityError<T>) -> Self {
+    Error::CapacityExceeded
+  }
+}
+
+impl<T, const N: usize> ArrayLinkedList<T, N> {
+  pub fn capacity(&self) -> usize {
+    N
+  }
+
+  pub fn is_full(&self) -> bool {
+    self.size == N
+  }
+
+  pub fn push_back_or_return(&mut self, elem: T) -> Result<NodePointer, CapacityError<T>> {
+    self.insert_between(elem, self.tail, NONE)
+  }
+
+  pub fn push_front_or_return(&mut self, elem: T) -> Result<NodePointer, CapacityError<T>> {
+    self.insert_between(elem, NONE, self.head)
+  }
+
+  // A stale pointer gives Ok(None), like insert_before does.
+  pub fn insert_before_or_return(
+    &mut self,
+    n: &NodePointer,
+    elem: T,
+  ) -> Result<Option<NodePointer>, CapacityError<T>> {
+    match self.node(n) {
+      Some(node) => self.insert_between(elem, node.prev, n.index).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  pub fn insert_after_or_return(
+    &mut self,
+    n: &NodePointer,
+    elem: T,
+  ) -> Result<Option<NodePointer>, CapacityError<T>> {
+    match self.node(n) {
+      Some(node) => self.insert_between(elem, n.index, node.next).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  // Moves every node of other to the back of this list, and returns a
+  // map from each moved node's old pointer to its new one. The nodes go
+  // straight into free slots here in one pass over other; only the map
+  // allocates. Panics before moving anything if they won't all fit.
+  pub fn append_with_remap(&mut self, other: &mut Self) -> HashMap<NodePointer, NodePointer> {
+    let mut remap = HashMap::with_capacity(other.size);
+    self.adopt(other, self.tail, NONE, |old, new| {
+      remap.insert(old, new);
+    });
+    remap
+  }
+
+  // Cuts the list in two just before n, and returns the part that
+  // starts at n along with a map from the old pointers of its nodes to
+  // their pointers in the new list.
+  pub fn split_off_with_remap(&mut self, n: &NodePointer) -> (Self, HashMap<NodePointer, NodePointer>) {
+    let mut remap = HashMap::new();
+    let rest = self.cut(n, |old, new| {
+      remap.insert(old, new);
+    });
+    (rest, remap)
+  }
+
+  // Moves every node of other in right after n, the same way as
+  // append_with_remap. A stale n moves nothing and gives an empty map.
+  pub fn splice_after_with_remap(
+    &mut self,
+    n: &NodePointer,
+    other: &mut Self,
+  ) -> HashMap<NodePointer, NodePointer> {
+    let mut remap = HashMap::new();
+    if let Some(node) = self.node(n) {
+      let next = node.next;
+      remap.reserve(other.size);
+      self.adopt(other, n.index, next, |old, new| {
+        remap.insert(old, new);
+      });
+    }
+    remap
+  }
+
+  // Moves every node of other, in order, into free slots here in
+  // between the nodes at prev and next, which have to be neighbours.
+  // moved hears about each node's old and new pointer. other is left
+  // empty, with every slot freed.
+  fn adopt<F>(&mut self, other: &mut Self, prev: usize, next: usize, mut moved: F)
+  where F: FnMut(NodePointer, NodePointer)
+  {
+    assert!(self.size + other.size <= N, "ArrayLinkedList can't hold more than {N} nodes");
+    let mut prev = prev;
+    while let Some((old, elem)) = other.take_head() {
+      let at = self.place(BodyNode { elem, next: NONE, prev });
+      self.set_next(prev, at);
+      moved(old, NodePointer { index: at, generation: self.spine[at].generation, list: self.id });
+      prev = at;
+    }
+    self.set_next(prev, next);
+    self.set_prev(next, prev);
+  }
+
+  // Takes the nodes from n to the tail out into a list of their own.
+  // moved hears about each node's old and new pointer. A stale n moves
+  // nothing.
+  fn cut<F>(&mut self, n: &NodePointer, mut moved: F) -> Self
+  where F: FnMut(NodePointer, NodePointer)
+  {
+    let mut rest = Self::new();
+    let last = match self.node(n) {
+      Some(node) => node.prev,
+      None => return rest,
+    };
+
+    self.set_next(last, NONE);
+    self.tail = last;
+
+    let mut prev = NONE;
+    let mut curr = n.index;
+    while curr != NONE {
+      let (old, node) = self.free(curr);
+      curr = node.next;
+
+      let at = rest.place(BodyNode { elem: node.elem, next: NONE, prev });
+      rest.set_next(prev, at);
+      moved(old, NodePointer { index: at, generation: rest.spine[at].generation, list: rest.id });
+      prev = at;
+    }
+    rest.tail = prev;
+    rest
+  }
+
+  // Unlinks the head, frees its slot and hands back its element along
+  // with the pointer it had.
+  fn take_head(&mut self) -> Option<(NodePointer, T)> {
+    if self.head == NONE {
+      return None;
+    }
+
+    let (old, node) = self.free(self.head);
+    self.head = node.next;
+    self.set_prev(node.next, NONE);
+    Some((old, node.elem))
+  }
+
+  // Empties slot i and puts it on the free list, without touching any
+  // links. Bumping the generation makes old pointers to it stale.
+  fn free(&mut self, i: usize) -> (NodePointer, BodyNode<T>) {
+    let slot = &mut self.spine[i];
+    let node = slot.node.take().expect("a link leads to an empty slot");
+    let old = NodePointer { index: i, generation: slot.generation, list: self.id };
+    slot.generation = slot.generation.wrapping_add(1);
+    self.free_list[self.free_len] = i;
+    self.free_len += 1;
+    self.size -= 1;
+    (old, node)
+  }
+
+  // Puts a node in a free slot, which the caller has made sure there
+  // is, and counts it. Links are left to the caller.
+  fn place(&mut self, node: BodyNode<T>) -> usize {
+    self.free_len -= 1;
+    let i = self.free_list[self.free_len];
+    self.spine[i].node = Some(node);
+    self.size += 1;
+    i
+  }
+
+  fn node(&self, n: &NodePointer) -> Option<&BodyNode<T>> {
+    if n.list != self.id {
+      return None;
+    }
+    let slot = self.spine.get(n.index)?;
+    if slot.generation != n.generation {
+      return None;
+    }
+    slot.node.as_ref()
+  }
+
+  fn node_mut(&mut self, n: &NodePointer) -> Option<&mut BodyNode<T>> {
+    if n.list != self.id {
+      return None;
+    }
+    let slot = self.spine.get_mut(n.index)?;
+    if slot.generation != n.generation {
+      return None;
+    }
+    slot.node.as_mut()
+  }
+
+  fn node_at(&mut self, i: usize) -> &mut BodyNode<T> {
+    match &mut self.spine[i].node {
+      None => panic!("a link leads to an empty slot"),
+      Some(node) => node,
+    }
+  }
+
+  fn pointer_at(&self, i: usize) -> Option<NodePointer> {
+    if i == NONE {
+      return None;
+    }
+    Some(NodePointer { index: i, generation: self.spine[i].generation, list: self.id })
+  }
+
+  fn set_next(&mut self, i: usize, next: usize) {
+    if i == NONE {
+      self.head = next;
+    } else {
+      self.node_at(i).next = next;
+    }
+  }
+
+  fn set_prev(&mut self, i: usize, prev: usize) {
+    if i == NONE {
+      self.tail = prev;
+    } else {
+      self.node_at(i).prev = prev;
+    }
+  }
+
+  fn insert_between(&mut self, elem: T, p: usize, n: usize) -> Result<NodePointer, CapacityError<T>> {
+    if self.free_len == 0 {
+      return Err(CapacityError(elem));
+    }
+
+    self.free_len -= 1;
+    let insert_at = self.free_list[self.free_len];
+    self.spine[insert_at].node = Some(BodyNode { elem, next: n, prev: p });
+
+    self.set_prev(n, insert_at);
+    self.set_next(p, insert_at);
+
+    self.size += 1;
+    Ok(NodePointer { index: insert_at, generation: self.spine[insert_at].generation, list: self.id })
+  }
+
+  // The DLL methods have nowhere to put the error.
+  fn expect_room(result: Result<NodePointer, CapacityError<T>>) -> NodePointer {
+    match result {
+      Ok(ptr) => ptr,
+      Err(_) => panic!("ArrayLinkedList can't hold more than {N} nodes"),
+    }
+  }
+}
+
+impl<T, const N: usize> DLL<T> for ArrayLinkedList<T, N> {
+  type Pointer = NodePointer;
+  type Ref<'a> = &'a T where T: 'a;
+  type RefMut<'a> = &'a mut T where T: 'a;
+  type IterMut<'a> = ArrayIterMut<'a, T, N> where T: 'a;
+
+  fn new() -> Self {
+    ArrayLinkedList {
+      spine: core::array::from_fn(|_| Slot { generation: 0, node: None }),
+      size: 0,
+      head: NONE,
+      tail: NONE,
+      // Handed out from the top, so slot 0 goes first.
+      free_list: core::array::from_fn(|i| N - 1 - i),
+      free_len: N,
+      id: next_list_id(),
+    }
+  }
+
+  fn size(&self) -> usize {
+    self.size
+  }
+
+  fn max_size() -> Option<usize> {
+    Some(N)
+  }
+
+  // The _or_return versions give the element back. These are for when
+  // it isn't wanted, or the list is only known as a DLL.
+  fn try_push_back(&mut self, elem: T) -> Result<NodePointer, Error> {
+    Ok(self.push_back_or_return(elem)?)
+  }
+
+  fn try_push_front(&mut self, elem: T) -> Result<NodePointer, Error> {
+    Ok(self.push_front_or_return(elem)?)
+  }
+
+  fn try_insert_before(&mut self, n: &NodePointer, elem: T) -> Result<NodePointer, Error> {
+    self.insert_before_or_return(n, elem)?.ok_or(Error::StalePointer)
+  }
+
+  fn try_insert_after(&mut self, n: &NodePointer, elem: T) -> Result<NodePointer, Error> {
+    self.insert_after_or_return(n, elem)?.ok_or(Error::StalePointer)
+  }
+
+  fn get(&self, n: &NodePointer) -> Option<&T> {
+    self.node(n).map(|node| &node.elem)
+  }
+
+  fn get_mut(&mut self, n: &NodePointer) -> Option<&mut T> {
+    self.node_mut(n).map(|node| &mut node.elem)
+  }
+
+  fn replace_val(&mut self, n: &NodePointer, elem: T) {
+    if let Some(node) = self.node_mut(n) {
+      node.elem = elem;
+    }
+  }
+
+  fn remove(&mut self, n: &NodePointer) -> Option<T> {
+    self.node(n)?;
+
+    let (_, existing_node) = self.free(n.index);
+
+    self.set_prev(existing_node.next, existing_node.prev);
+    self.set_next(existing_node.prev, existing_node.next);
+
+    Some(existing_node.elem)
+  }
+
+  fn insert_before(&mut self, n: &NodePointer, elem: T) -> Option<NodePointer> {
+    let prev = self.node(n)?.prev;
+    Some(Self::expect_room(self.insert_between(elem, prev, n.index)))
+  }
+
+  fn insert_after(&mut self, n: &NodePointer, elem: T) -> Option<NodePointer> {
+    let next = self.node(n)?.next;
+    Some(Self::expect_room(self.insert_between(elem, n.index, next)))
+  }
+
+  // Moves other's nodes into free slots here in one pass, so this is
+  // O(other) and the pointers into other change; append_with_remap says
+  // what to. Panics before moving anything if they won't all fit.
+  fn append(&mut self, other: &mut Self) {
+    self.adopt(other, self.tail, NONE, |_, _| {});
+  }
+
+  // O(moved nodes), and their pointers change; see split_off_with_remap.
+  fn split_off(&mut self, n: &NodePointer) -> Self {
+    self.cut(n, |_, _| {})
+  }
+
+  // Panics before moving anything if other won't fit, see append.
+  fn splice_after(&mut self, n: &NodePointer, other: &mut Self) {
+    if let Some(node) = self.node(n) {
+      let next = node.next;
+      self.adopt(other, n.index, next, |_, _| {});
+    }
+  }
+
+  fn prepend(&mut self, other: &mut Self) {
+    self.adopt(other, NONE, self.head, |_, _| {});
+  }
+
+  fn push_back(&mut self, elem: T) -> NodePointer {
+    Self::expect_room(self.push_back_or_return(elem))
+  }
+
+  fn push_front(&mut self, elem: T) -> NodePointer {
+    Self::expect_room(self.push_front_or_return(elem))
+  }
+
+  fn pop_front(&mut self) -> Option<T> {
+    let head = self.head()?;
+    self.remove(&head)
+  }
+
+  fn pop_back(&mut self) -> Option<T> {
+    let tail = self.tail()?;
+    self.remove(&tail)
+  }
+
+  fn peek_front(&self) -> Option<&T> {
+    self.get(&self.head()?)
+  }
+
+  fn peek_back(&self) -> Option<&T> {
+    self.get(&self.tail()?)
+  }
+
+  // The node was just freed, so pushing it back always has room.
+  fn move_back(&mut self, n: &mut NodePointer) {
+    if let Some(elem) = self.remove(n) {
+      *n = self.push_back(elem);
+    }
+  }
+
+  fn move_front(&mut self, n: &mut NodePointer) {
+    if let Some(elem) = self.remove(n) {
+      *n = self.push_front(elem);
+    }
+  }
+
+  fn next_node(&self, ptr: &NodePointer) -> Option<NodePointer> {
+    let next = self.node(ptr)?.next;
+    self.pointer_at(next)
+  }
+
+  fn prev_node(&self, ptr: &NodePointer) -> Option<NodePointer> {
+    let prev = self.node(ptr)?.prev;
+    self.pointer_at(prev)
+  }
+
+  fn iter_mut(&mut self) -> ArrayIterMut<'_, T, N> {
+    ArrayIterMut {
+      front: self.head,
+      back: self.tail,
+      remaining: self.size,
+      spine: self.spine.as_mut_ptr(),
+      _list: PhantomData,
+    }
+  }
+
+  fn head(&self) -> Option<NodePointer> {
+    self.pointer_at(self.head)
+  }
+
+  fn tail(&self) -> Option<NodePointer> {
+    self.pointer_at(self.tail)
+  }
+}
+
+// Walks the spine through a raw pointer, the same way as
+// VectorLinkedList's VecIterMut.
+pub struct ArrayIterMut<'a, T, const N: usize> {
+  front: usize,
+  back: usize,
+  remaining: usize,
+  spine: *mut Slot<T>,
+  _list: PhantomData<&'a mut [Slot<T>; N]>,
+}
+
+impl<'a, T, const N: usize> ArrayIterMut<'a, T, N> {
+  // Hands out the element in slot i, along with its next and prev.
+  fn take(&mut self, i: usize) -> (&'a mut T, usize, usize) {
+    self.remaining -= 1;
+
+    // SAFETY: spine came from the &mut borrowed for 'a, and i is a
+    // linked slot, so it is in bounds and holds a node. remaining stops
+    // front and back from reaching the same slot twice, so the &muts
+    // handed out never overlap.
+    let node = unsafe { (*self.spine.add(i)).node.as_mut() };
+    match node {
+      Some(node) => (&mut node.elem, node.next, node.prev),
+      None => panic!("a link leads to an empty slot"),
+    }
+  }
+}
+
+impl<'a, T, const N: usize> Iterator for ArrayIterMut<'a, T, N> {
+  type Item = &'a mut T;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 || self.front == NONE {
+      return None;
+    }
+
+    let (elem, next, _) = self.take(self.front);
+    self.front = next;
+    Some(elem)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for ArrayIterMut<'_, T, N> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 || self.back == NONE {
+      return None;
+    }
+
+    let (elem, _, prev) = self.take(self.back);
+    self.back = prev;
+    Some(elem)
+  }
+}
+
+impl<T, const N: usize> ExactSizeIterator for ArrayIterMut<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for ArrayIterMut<'_, T, N> {}
+
+impl<T: fmt::Display, const N: usize> fmt::Display for ArrayLinkedList<T, N> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut node = self.head();
+    let mut first = true;
+
+    // Written straight to f, since building Strings would allocate.
+    while let Some(ptr) = node {
+      node = self.next_node(&ptr);
+      if !first {
+        write!(f, " <-> ")?;
+      }
+      first = false;
+      write!(f, "[{} - {}]", ptr.index, self.get(&ptr).unwrap())?;
+    }
+
+    Ok(())
+  }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for ArrayLinkedList<T, N> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for ArrayLinkedList<T, N> {
+  fn eq(&self, other: &Self) -> bool {
+    self.size == other.size && self.iter().eq(other.iter())
+  }
+}
+
+impl<T: Eq, const N: usize> Eq for ArrayLinkedList<T, N> {}
+
+impl<T: Hash, const N: usize> Hash for ArrayLinkedList<T, N> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write_usize(self.size);
+    for elem in self.iter() {
+      elem.hash(state);
+    }
+  }
+}
+
+impl<T, const N: usize> Default for ArrayLinkedList<T, N> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+// Panics once the list is full, like push_back.
+impl<T, const N: usize> Extend<T> for ArrayLinkedList<T, N> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    for elem in iter {
+      self.push_back(elem);
+    }
+  }
+}
+
+impl<T, const N: usize> FromIterator<T> for ArrayLinkedList<T, N> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    let mut list = Self::new();
+    list.extend(iter);
+    list
+  }
+}
+
+impl<T, const N: usize> IntoIterator for ArrayLinkedList<T, N> {
+  type Item = T;
+  type IntoIter = super::DLLIntoIter<T, Self>;
+  fn into_iter(self) -> Self::IntoIter {
+    super::DLLIntoIter {
+      _wokka: PhantomData,
+      list: self,
+    }
+  }
+}
+
+// Enough room for everything the shared tests push.
+#[cfg(test)]
+type TestList<T> = ArrayLinkedList<T, 16>;
+
+#[cfg(test)]
+crate::linked_list::macros::dll_tests!(TestList);
+
+#[cfg(test)]
+crate::dll_conformance!(TestList<u32>);
+
+
+#[cfg(test)]
+mod capacity_test {
+  use super::*;
+
+  #[test]
+  fn full() {
+    let mut l: ArrayLinkedList<i32, 3> = ArrayLinkedList::new();
+    assert_eq!(l.capacity(), 3);
+    let first = l.push_back_or_return(1).unwrap();
+    l.push_back_or_return(2).unwrap();
+    l.push_front_or_return(0).unwrap();
+    assert!(l.is_full());
+
+    assert_eq!(l.push_back_or_return(3), Err(CapacityError(3)));
+    assert_eq!(l.push_front_or_return(-1).map_err(CapacityError::into_inner), Err(-1));
+    assert_eq!(l.insert_after_or_return(&first, 10), Err(CapacityError(10)));
+    assert_eq!(l.size(), 3);
+
+    // Moving doesn't need any room.
+    let mut ptr = first;
+    l.move_front(&mut ptr);
+    l.move_back(&mut ptr);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![0, 2, 1]);
+
+    // Removing makes room again.
+    assert_eq!(l.remove(&ptr), Some(1));
+    assert_eq!(l.insert_before_or_return(&ptr, 5), Ok(None));
+    let two = l.tail().unwrap();
+    assert!(l.insert_before_or_return(&two, 1).unwrap().is_some());
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+  }
+
+  #[test]
+  #[should_panic(expected = "can't hold more than 2 nodes")]
+  fn push_past_full_panics() {
+    let mut l: ArrayLinkedList<i32, 2> = ArrayLinkedList::new();
+    l.push_back(1);
+    l.push_back(2);
+    l.push_back(3);
+  }
+
+  #[test]
+  fn append_checks_room_first() {
+    let mut l: ArrayLinkedList<i32, 3> = ArrayLinkedList::new();
+    let mut other: ArrayLinkedList<i32, 3> = ArrayLinkedList::new();
+    l.push_back(1);
+    l.push_back(2);
+    other.push_back(3);
+    other.push_back(4);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| l.append(&mut other)));
+    assert!(result.is_err());
+    assert_eq!(l.size(), 2);
+    assert_eq!(other.size(), 2);
+
+    other.pop_back();
+    l.append(&mut other);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+  }
+
+  #[test]
+  fn moves_hand_back_remaps() {
+    let mut l: ArrayLinkedList<i32, 4> = ArrayLinkedList::new();
+    let mut other: ArrayLinkedList<i32, 4> = ArrayLinkedList::new();
+    let one = l.push_back(1);
+    let theirs = [other.push_back(2), other.push_back(3)];
+
+    let remap = l.append_with_remap(&mut other);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(l.get(&remap[&theirs[1]]), Some(&3));
+    assert_eq!(other.size(), 0);
+    assert_eq!(other.get(&theirs[0]), None);
+    assert!(other.try_push_back(4).is_ok());
+
+    let two = remap[&theirs[0]];
+    let (rest, remap) = l.split_off_with_remap(&two);
+    assert_eq!(rest.iter().copied().collect::<Vec<_>>(), vec![2, 3]);
+    assert_eq!(rest.get(&remap[&two]), Some(&2));
+    assert_eq!(l.get(&two), None);
+
+    let mut rest = rest;
+    let remap = l.splice_after_with_remap(&one, &mut rest);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(remap.len(), 2);
+    assert_eq!(l.free_len, 1);
+  }
+
+  #[test]
+  fn dll_errors() {
+    use crate::error::Error;
+
+    let mut l: ArrayLinkedList<i32, 2> = ArrayLinkedList::new();
+    let one = l.try_push_back(1).unwrap();
+    l.try_push_front(0).unwrap();
+    assert!(!l.has_room(1));
+    assert_eq!(l.try_push_back(2), Err(Error::CapacityExceeded));
+    assert_eq!(l.try_push_front(2), Err(Error::CapacityExceeded));
+    assert_eq!(l.try_insert_after(&one, 2), Err(Error::CapacityExceeded));
+
+    // Stale is reported before full.
+    let mut stale = l.split_off(&one);
+    assert_eq!(l.try_insert_before(&one, 2), Err(Error::StalePointer));
+
+    let mut other: ArrayLinkedList<i32, 2> = ArrayLinkedList::new();
+    other.push_back(5);
+    other.push_back(6);
+    assert_eq!(stale.try_append(&mut other), Err(Error::CapacityExceeded));
+    assert_eq!((stale.size(), other.size()), (1, 2));
+    assert_eq!(l.try_append(&mut stale), Ok(()));
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![0, 1]);
+  }
+
+  #[test]
+  fn zero_sized() {
+    let mut l: ArrayLinkedList<i32, 0> = ArrayLinkedList::new();
+    assert_eq!(l.push_back_or_return(1), Err(CapacityError(1)));
+    assert_eq!(l.pop_front(), None);
+    assert_eq!(l.iter_mut().next(), None);
+  }
+}
@@ -1,15 +1,23 @@
-use crate::linked_list::DLL;
-use std::marker::PhantomData;
-use std::cell::RefCell;
-use std::fmt::{Debug, Error, Formatter, Display};
-use std::mem;
-use std::fmt;
-use std::rc::{Rc, Weak};
+use crate::linked_list::{DLL, InvariantError, debug_validate};
+use alloc::format;
+use alloc::rc::{Rc, Weak};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::cell::{Ref, RefCell, RefMut};
+use core::cmp::Ordering;
+use core::fmt::{Debug, Error, Formatter, Display};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::{Deref, DerefMut};
 
 pub struct BodyNode<T> {
   elem: T,
   next: StrongNodePointer<T>,
   prev: WeakNodePointer<T>,
+  owner: Rc<Owner>,
 }
 
 impl<T: Debug> Debug for BodyNode<T> {
@@ -23,12 +31,154 @@ impl<T: Debug> Debug for BodyNode<T> {
 type StrongNodePointer<T> = Option<Rc<RefCell<BodyNode<T>>>>;
 type WeakNodePointer<T> = Option<Weak<RefCell<BodyNode<T>>>>;
 
+// Which list a node is in, so a pointer from another list is turned
+// away. Every list has an Owner, and every node holds the Owner of the
+// list it was linked into. Moving all of a list's nodes at once doesn't
+// touch them: the emptied list's Owner is pointed at the one taking
+// them in, and the emptied list gets a new Owner. So a node's list is
+// the one whose Owner is at the end of that chain.
+struct Owner {
+  merged_into: RefCell<Option<Rc<Owner>>>,
+}
+
+impl Owner {
+  fn new() -> Rc<Owner> {
+    Rc::new(Owner { merged_into: RefCell::new(None) })
+  }
+
+  // The end of the chain. Everything on the way is pointed straight at
+  // it, so the next lookup from there is a single step.
+  fn root(owner: &Rc<Owner>) -> Rc<Owner> {
+    let mut root = Rc::clone(owner);
+    loop {
+      let next = root.merged_into.borrow().clone();
+      match next {
+        None => break,
+        Some(next) => root = next,
+      }
+    }
+
+    let mut at = Rc::clone(owner);
+    while !Rc::ptr_eq(&at, &root) {
+      let next = at.merged_into.replace(Some(Rc::clone(&root)));
+      at = next.expect("the chain ends at the root");
+    }
+    root
+  }
+}
+
 pub struct CellLinkedList<T> {
   head: StrongNodePointer<T>,
   tail: StrongNodePointer<T>,
   size: usize,
+  // Never merged into another, see Owner.
+  owner: Rc<Owner>,
+}
+
+// A borrow of one element, handed out by get and peek. It goes through
+// the node's RefCell, so it is tracked like any other borrow, and it
+// holds on to the node itself, so it can't outlive the memory it points
+// into.
+pub struct CellRef<'a, T> {
+  // Declared before the Rc, so it is dropped first.
+  elem: Ref<'a, T>,
+  _node: Rc<RefCell<BodyNode<T>>>,
+}
+
+impl<'a, T> CellRef<'a, T> {
+  fn new(node: Rc<RefCell<BodyNode<T>>>) -> Self {
+    // SAFETY: the RefCell lives in the Rc's allocation, and this guard
+    // owns that Rc until after the borrow below is gone. So the
+    // reference stays valid for as long as anything can use it.
+    let cell: &'a RefCell<BodyNode<T>> = unsafe { &*Rc::as_ptr(&node) };
+    CellRef {
+      elem: Ref::map(cell.borrow(), |n| &n.elem),
+      _node: node,
+    }
+  }
+}
+
+impl<T> Deref for CellRef<'_, T> {
+  type Target = T;
+  fn deref(&self) -> &T {
+    &self.elem
+  }
+}
+
+// The get_mut counterpart to CellRef.
+pub struct CellRefMut<'a, T> {
+  elem: RefMut<'a, T>,
+  _node: Rc<RefCell<BodyNode<T>>>,
 }
 
+impl<'a, T> CellRefMut<'a, T> {
+  fn new(node: Rc<RefCell<BodyNode<T>>>) -> Self {
+    // SAFETY: same as CellRef::new.
+    let cell: &'a RefCell<BodyNode<T>> = unsafe { &*Rc::as_ptr(&node) };
+    CellRefMut {
+      elem: RefMut::map(cell.borrow_mut(), |n| &mut n.elem),
+      _node: node,
+    }
+  }
+}
+
+impl<T> Deref for CellRefMut<'_, T> {
+  type Target = T;
+  fn deref(&self) -> &T {
+    &self.elem
+  }
+}
+
+impl<T> DerefMut for CellRefMut<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    &mut self.elem
+  }
+}
+
+// Hands out a CellRefMut per node. Holding the list's &mut means nothing
+// else can borrow a node meanwhile, and each node is only reached once,
+// so none of the borrow_mut calls can fail.
+pub struct CellIterMut<'a, T> {
+  front: StrongNodePointer<T>,
+  back: StrongNodePointer<T>,
+  remaining: usize,
+  _list: PhantomData<&'a mut CellLinkedList<T>>,
+}
+
+impl<'a, T> Iterator for CellIterMut<'a, T> {
+  type Item = CellRefMut<'a, T>;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    let node = self.front.take()?;
+    self.front = node.borrow().next.clone();
+    self.remaining -= 1;
+    Some(CellRefMut::new(node))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<T> DoubleEndedIterator for CellIterMut<'_, T> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    let node = self.back.take()?;
+    self.back = convert_weak(&node.borrow().prev);
+    self.remaining -= 1;
+    Some(CellRefMut::new(node))
+  }
+}
+
+impl<T> ExactSizeIterator for CellIterMut<'_, T> {}
+impl<T> FusedIterator for CellIterMut<'_, T> {}
+
 fn convert_weak<T>(weak_ptr: &WeakNodePointer<T>) -> StrongNodePointer<T> {
   let result = weak_ptr.clone().map(|t| t.upgrade());
   if let Some(None) = result {
@@ -39,16 +189,38 @@ fn convert_weak<T>(weak_ptr: &WeakNodePointer<T>) -> StrongNodePointer<T> {
 }
 
 impl<T> CellLinkedList<T> {
-  fn insert_after(
+  // The node a pointer refers to, if it is still in this list.
+  fn live(&self, ptr: &WeakNodePointer<T>) -> StrongNodePointer<T> {
+    let node = convert_weak(ptr)?;
+    let mine = Rc::ptr_eq(&Owner::root(&node.borrow().owner), &self.owner);
+    mine.then_some(node)
+  }
+
+  // other's nodes have all just been moved here. Their Owner now leads
+  // to this list's, and other starts again with one of its own.
+  fn take_owner(&self, other: &mut Self) {
+    let old = mem::replace(&mut other.owner, Owner::new());
+    *old.merged_into.borrow_mut() = Some(Rc::clone(&self.owner));
+  }
+
+  // Hands back the node at ptr and moves ptr on to the next one.
+  fn advance(ptr: &mut StrongNodePointer<T>) -> Rc<RefCell<BodyNode<T>>> {
+    let node = ptr.take().expect("advanced past the end");
+    *ptr = node.borrow().next.clone();
+    node
+  }
+
+  fn link_after(
     &mut self,
     elem: T,
     n: &StrongNodePointer<T>,
   ) -> WeakNodePointer<T> {
     let new_node = RefCell::new(
       BodyNode {
-        elem: elem,
+        elem,
         next: None,
         prev: None,
+        owner: Rc::clone(&self.owner),
       }
     );
 
@@ -75,7 +247,7 @@ impl<T> CellLinkedList<T> {
       },
 
       Some(ptr) => {
-        let new_next_node = &ptr.borrow().next.as_ref().map(|ptr| Rc::clone(ptr));
+        let new_next_node = &ptr.borrow().next.as_ref().map(Rc::clone);
         ptr.borrow_mut().next = Some(new_node_ptr.clone());
 
         match new_next_node {
@@ -93,15 +265,15 @@ impl<T> CellLinkedList<T> {
         new_node_ptr.borrow_mut().next = new_next_node.clone();
 
         // Set the new node's prev to a weak pointer to the cursor node
-        new_node_ptr.borrow_mut().prev = n.as_ref().map(|t| Rc::downgrade(t));
+        new_node_ptr.borrow_mut().prev = n.as_ref().map(Rc::downgrade);
       },
     }
 
-    
+    debug_validate(self.size, || self.check_invariants());
     Some(Rc::downgrade(&new_node_ptr))
   }
 
-  fn remove(
+  fn unlink(
     p: &mut StrongNodePointer<T>,
     h: &mut StrongNodePointer<T>,
     t: &mut StrongNodePointer<T>,
@@ -110,17 +282,17 @@ impl<T> CellLinkedList<T> {
     *size -= 1;
 
     let ptr = match p {
-      None => panic!("DO NOT DO THIS"),
+      None => panic!("unlink needs a node"),
       Some(i) => i,
     };
 
-    let prior_ptr = ptr.borrow().prev.as_ref().map(|ptr| Weak::clone(ptr));
-    let next_ptr = ptr.borrow().next.as_ref().map(|ptr| Rc::clone(ptr));
+    let prior_ptr = ptr.borrow().prev.as_ref().map(Weak::clone);
+    let next_ptr = ptr.borrow().next.as_ref().map(Rc::clone);
 
     if let Some(p_ptr) = &prior_ptr {
-      p_ptr.upgrade().unwrap().borrow_mut().next = next_ptr.as_ref().map(|p| Rc::clone(p));
+      p_ptr.upgrade().unwrap().borrow_mut().next = next_ptr.as_ref().map(Rc::clone);
     } else {
-      *h = next_ptr.as_ref().map(|p| Rc::clone(p));
+      *h = next_ptr.as_ref().map(Rc::clone);
     }
 
     if let Some(n_ptr) = &next_ptr {
@@ -131,23 +303,27 @@ impl<T> CellLinkedList<T> {
 
     ptr.borrow_mut().next = None;
     ptr.borrow_mut().prev = None;
-    let curr_ptr = mem::replace(p, None).unwrap();
+    let curr_ptr = p.take().unwrap();
 
     match Rc::try_unwrap(curr_ptr) {
       Ok(ref_cell) => ref_cell.into_inner().elem,
-      _ => panic!("PANIC"),
+      _ => panic!("an unlinked node is still shared"),
     }
   }
 }
 
 impl<T> DLL<T> for CellLinkedList<T> {
   type Pointer = WeakNodePointer<T>;
+  type Ref<'a> = CellRef<'a, T> where T: 'a;
+  type RefMut<'a> = CellRefMut<'a, T> where T: 'a;
+  type IterMut<'a> = CellIterMut<'a, T> where T: 'a;
 
   fn new() -> Self {
     CellLinkedList {
       head: None,
       tail: None,
       size: 0,
+      owner: Owner::new(),
     }
   }
 
@@ -155,131 +331,346 @@ impl<T> DLL<T> for CellLinkedList<T> {
     self.size
   }
 
-  fn get(&self, weak_ptr: &Self::Pointer) -> Option<&T> {
-    let ptr = convert_weak(weak_ptr);
+  fn get(&self, weak_ptr: &Self::Pointer) -> Option<CellRef<'_, T>> {
+    self.live(weak_ptr).map(CellRef::new)
+  }
 
-    match ptr {
-      None => None,
-      Some(n) => {
-        unsafe {
-          Some(&(*n.as_ptr()).elem)
-        }
-      }
+  fn get_mut(&mut self, weak_ptr: &Self::Pointer) -> Option<CellRefMut<'_, T>> {
+    self.live(weak_ptr).map(CellRefMut::new)
+  }
+
+  fn replace_val(&mut self, ptr: &Self::Pointer, elem: T) {
+    if let Some(mut f) = self.get_mut(ptr) {
+      *f = elem;
     }
   }
 
-  fn get_mut(&mut self, weak_ptr: &Self::Pointer) -> Option<&mut T> {
-    let ptr = convert_weak(weak_ptr);
+  fn remove(&mut self, ptr: &Self::Pointer) -> Option<T> {
+    let mut node = self.live(ptr);
+    node.as_ref()?;
+    let elem = Self::unlink(&mut node, &mut self.head, &mut self.tail, &mut self.size);
+    debug_validate(self.size, || self.check_invariants());
+    Some(elem)
+  }
 
-    match ptr {
-      None => None,
-      Some(n) => {
-        unsafe {
-          Some(&mut (*n.as_ptr()).elem)
-        }
-      }
+  fn insert_before(&mut self, ptr: &Self::Pointer, elem: T) -> Option<Self::Pointer> {
+    let node = self.live(ptr)?;
+    let prev = convert_weak(&node.borrow().prev);
+    Some(self.link_after(elem, &prev))
+  }
+
+  fn insert_after(&mut self, ptr: &Self::Pointer, elem: T) -> Option<Self::Pointer> {
+    let node = self.live(ptr)?;
+    Some(self.link_after(elem, &Some(node)))
+  }
+
+  // Relinks the ends of the two lists, so it is O(1) and pointers into
+  // other keep working.
+  fn append(&mut self, other: &mut Self) {
+    let other_head = match other.head.take() {
+      None => return,
+      Some(head) => head,
+    };
+
+    match &self.tail {
+      None => self.head = Some(Rc::clone(&other_head)),
+      Some(tail) => {
+        other_head.borrow_mut().prev = Some(Rc::downgrade(tail));
+        tail.borrow_mut().next = Some(other_head);
+      },
     }
+
+    self.tail = other.tail.take();
+    self.size += other.size;
+    other.size = 0;
+    self.take_owner(other);
+    debug_validate(self.size, || self.check_invariants());
   }
 
-  fn replace_val(&mut self, ptr: &Self::Pointer, elem: T) {
-    let n = match ptr {
-      None => panic!("DO NOT DO THIS"),
-      Some(i) => i,
+  // O(1), like append.
+  fn prepend(&mut self, other: &mut Self) {
+    let other_tail = match other.tail.take() {
+      None => return,
+      Some(tail) => tail,
     };
 
-    if let Some(f) = self.get_mut(ptr) {
-      *f = elem;
+    match &self.head {
+      None => self.tail = Some(Rc::clone(&other_tail)),
+      Some(head) => {
+        head.borrow_mut().prev = Some(Rc::downgrade(&other_tail));
+        other_tail.borrow_mut().next = Some(Rc::clone(head));
+      },
     }
+
+    self.head = other.head.take();
+    self.size += other.size;
+    other.size = 0;
+    self.take_owner(other);
+    debug_validate(self.size, || self.check_invariants());
+  }
+
+  // O(moved nodes). The relinking is O(1), but the size of each half
+  // has to be counted, which walks from ptr to the tail, and the nodes
+  // on the way are handed to the new list's Owner.
+  fn split_off(&mut self, ptr: &Self::Pointer) -> Self {
+    let mut rest = Self::new();
+    let node = match self.live(ptr) {
+      None => return rest,
+      Some(node) => node,
+    };
+
+    let mut moved = 0;
+    let mut curr = Some(Rc::clone(&node));
+    while let Some(n) = curr {
+      moved += 1;
+      n.borrow_mut().owner = Rc::clone(&rest.owner);
+      curr = n.borrow().next.as_ref().map(Rc::clone);
+    }
+
+    let prev = convert_weak(&node.borrow_mut().prev.take());
+    match &prev {
+      None => self.head = None,
+      Some(prev) => prev.borrow_mut().next = None,
+    }
+
+    rest.tail = mem::replace(&mut self.tail, prev);
+    rest.head = Some(node);
+    rest.size = moved;
+    self.size -= moved;
+    debug_validate(self.size, || self.check_invariants());
+    debug_validate(rest.size, || rest.check_invariants());
+    rest
+  }
+
+  fn splice_after(&mut self, ptr: &Self::Pointer, other: &mut Self) {
+    let node = match self.live(ptr) {
+      None => return,
+      Some(node) => node,
+    };
+    let (other_head, other_tail) = match (other.head.take(), other.tail.take()) {
+      (Some(head), Some(tail)) => (head, tail),
+      _ => return,
+    };
+
+    let next = node.borrow_mut().next.take();
+    match &next {
+      None => self.tail = Some(Rc::clone(&other_tail)),
+      Some(next) => next.borrow_mut().prev = Some(Rc::downgrade(&other_tail)),
+    }
+
+    other_tail.borrow_mut().next = next;
+    other_head.borrow_mut().prev = Some(Rc::downgrade(&node));
+    node.borrow_mut().next = Some(other_head);
+
+    self.size += other.size;
+    other.size = 0;
+    self.take_owner(other);
+    debug_validate(self.size, || self.check_invariants());
   }
 
   fn push_back(&mut self, elem: T) -> Self::Pointer {
-    let tail = self.tail.as_ref().map(|ptr| Rc::clone(ptr));
-    self.insert_after(elem, &tail)
+    let tail = self.tail.as_ref().map(Rc::clone);
+    self.link_after(elem, &tail)
   }
 
   fn push_front(&mut self, elem: T) -> Self::Pointer {
-    self.insert_after(elem, &None)
+    self.link_after(elem, &None)
   }
 
   fn pop_front(&mut self) -> Option<T> {
     let head = &mut self.head;
-    if let None = head {
+    if head.is_none() {
       return None;
     }
 
-    Some(Self::remove(&mut head.clone(), head, &mut self.tail, &mut self.size))
+    let elem = Self::unlink(&mut head.clone(), head, &mut self.tail, &mut self.size);
+    debug_validate(self.size, || self.check_invariants());
+    Some(elem)
   }
 
   fn pop_back(&mut self) -> Option<T> {
     let tail = &mut self.tail;
-    if let None = tail {
+    if tail.is_none() {
       return None;
     }
-    Some(Self::remove(&mut tail.clone(), &mut self.head, tail, &mut self.size))
+    let elem = Self::unlink(&mut tail.clone(), &mut self.head, tail, &mut self.size);
+    debug_validate(self.size, || self.check_invariants());
+    Some(elem)
   }
 
-  fn peek_front(&self) -> Option<&T> {
-    if let None = self.head {
-      return None;
-    }
-
-    self.get(&self.head.as_ref().map(|t| Rc::downgrade(t)))
+  fn peek_front(&self) -> Option<CellRef<'_, T>> {
+    self.head.clone().map(CellRef::new)
   }
 
-  fn peek_back(&self) -> Option<&T> {
-    if let None = self.tail {
-      return None;
-    }
-
-    self.get(&self.tail.as_ref().map(|t| Rc::downgrade(t)))
+  fn peek_back(&self) -> Option<CellRef<'_, T>> {
+    self.tail.clone().map(CellRef::new)
   }
 
+  // A stale pointer is left alone, and so is the list.
   fn move_back(&mut self, n: &mut Self::Pointer) {
-    let elem = Self::remove(
-      &mut convert_weak(n),
-      &mut self.head,
-      &mut self.tail,
-      &mut self.size,
-    );
-    let new_ptr = self.push_back(elem);
-    *n = new_ptr;
+    if let Some(elem) = self.remove(n) {
+      *n = self.push_back(elem);
+    }
+    debug_validate(self.size, || self.check_invariants());
   }
 
   fn move_front(&mut self, n: &mut Self::Pointer) {
-    let elem = Self::remove(
-      &mut convert_weak(n),
-      &mut self.head,
-      &mut self.tail,
-      &mut self.size
-    );
-    let new_ptr = self.push_front(elem);
-    *n = new_ptr;
+    if let Some(elem) = self.remove(n) {
+      *n = self.push_front(elem);
+    }
+    debug_validate(self.size, || self.check_invariants());
   }
 
+  // Stale pointers have no neighbours.
   fn next_node(&self, weak_ptr: &Self::Pointer) -> Option<Self::Pointer> {
-    let ptr = convert_weak(weak_ptr);
-    if let Some(p) = ptr {
-      let next = &p.borrow().next;
-      if let None = next {
-        return None;
+    let p = self.live(weak_ptr)?;
+    let next = &p.borrow().next;
+    next.as_ref().map(|n_ptr| Some(Rc::downgrade(n_ptr)))
+  }
+
+  fn prev_node(&self, weak_ptr: &Self::Pointer) -> Option<Self::Pointer> {
+    let p = self.live(weak_ptr)?;
+    let prev = &p.borrow().prev;
+    prev.as_ref().map(|p_ptr| Some(Weak::clone(p_ptr)))
+  }
+
+  fn iter_mut(&mut self) -> CellIterMut<'_, T> {
+    CellIterMut {
+      front: self.head.clone(),
+      back: self.tail.clone(),
+      remaining: self.size,
+      _list: PhantomData,
+    }
+  }
+
+  // Swaps every node's links around, walking from the head. Each node
+  // is only owned through its next link, so the one just done is held
+  // on to until the node after it links back to it.
+  fn reverse(&mut self) {
+    let mut done: StrongNodePointer<T> = None;
+    let mut curr = self.head.clone();
+    while let Some(node) = curr {
+      let next = node.borrow().next.clone();
+      {
+        let mut n = node.borrow_mut();
+        n.next = done.take();
+        n.prev = next.as_ref().map(Rc::downgrade);
       }
+      done = Some(node);
+      curr = next;
+    }
+    mem::swap(&mut self.head, &mut self.tail);
+    debug_validate(self.size, || self.check_invariants());
+  }
 
-      Some(next.as_ref().map(|ptr| Rc::downgrade(ptr)))
-    } else {
-      panic!("Should not happen")
+  // The same bottom up merge sort as VectorLinkedList's, over the next
+  // links, with the prev links put back at the end. O(1) extra space,
+  // and the nodes stay put, so pointers keep working. Overwriting a
+  // next link never drops the last strong reference to a node, since
+  // the node it led to is always the one p or q holds.
+  fn sort_by<F>(&mut self, mut cmp: F)
+  where F: FnMut(&T, &T) -> Ordering
+  {
+    if self.size < 2 {
+      return;
+    }
+
+    let mut width = 1;
+    loop {
+      let mut p = self.head.take();
+      let mut tail: StrongNodePointer<T> = None;
+      let mut merges = 0;
+
+      while p.is_some() {
+        merges += 1;
+        let mut q = p.clone();
+        let mut p_left = 0;
+        while p_left < width && q.is_some() {
+          p_left += 1;
+          Self::advance(&mut q);
+        }
+        let mut q_left = width;
+
+        loop {
+          let q_has = q_left > 0 && q.is_some();
+          // Ties go to p, the earlier run, which keeps it stable.
+          let take_p = match (p_left > 0, q_has) {
+            (false, false) => break,
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => {
+              let (a, b) = (p.as_ref().unwrap().borrow(), q.as_ref().unwrap().borrow());
+              cmp(&a.elem, &b.elem) != Ordering::Greater
+            },
+          };
+          let next = if take_p {
+            p_left -= 1;
+            Self::advance(&mut p)
+          } else {
+            q_left -= 1;
+            Self::advance(&mut q)
+          };
+          match &tail {
+            Some(t) => t.borrow_mut().next = Some(Rc::clone(&next)),
+            None => self.head = Some(Rc::clone(&next)),
+          }
+          tail = Some(next);
+        }
+        p = q;
+      }
+
+      if let Some(t) = &tail {
+        t.borrow_mut().next = None;
+      }
+      if merges <= 1 {
+        break;
+      }
+      width *= 2;
+    }
+
+    let mut prev: StrongNodePointer<T> = None;
+    let mut curr = self.head.clone();
+    while let Some(node) = curr {
+      node.borrow_mut().prev = prev.as_ref().map(Rc::downgrade);
+      curr = node.borrow().next.clone();
+      prev = Some(node);
     }
+    self.tail = prev;
+    debug_validate(self.size, || self.check_invariants());
   }
 
-  fn prev_node(&self, weak_ptr: &Self::Pointer) -> Option<Self::Pointer> {
-    let ptr = convert_weak(weak_ptr);
-    if let Some(p) = ptr {
-      match &p.borrow().prev {
-        None => None,
-        Some(p_ptr) => Some(Some(Weak::clone(p_ptr))),
+  fn check_invariants(&self) -> Result<(), InvariantError> {
+    let mut reachable = 0;
+    let mut prev: StrongNodePointer<T> = None;
+    let mut curr = self.head.clone();
+    while let Some(node) = curr {
+      let back = convert_weak(&node.borrow().prev);
+      let points_back = match (&back, &prev) {
+        (None, None) => true,
+        (Some(back), Some(prev)) => Rc::ptr_eq(back, prev),
+        _ => false,
+      };
+      if !points_back {
+        return Err(InvariantError::BrokenBackLink { position: reachable });
       }
-    } else {
-      panic!("NOOOOO!!")
+      curr = node.borrow().next.clone();
+      prev = Some(node);
+      reachable += 1;
     }
+
+    let tail_is_last = match (&self.tail, &prev) {
+      (None, None) => true,
+      (Some(tail), Some(last)) => Rc::ptr_eq(tail, last),
+      _ => false,
+    };
+    if !tail_is_last {
+      return Err(InvariantError::WrongTail);
+    }
+    if reachable != self.size {
+      return Err(InvariantError::SizeMismatch { size: self.size, reachable });
+    }
+
+    Ok(())
   }
 
   fn head(&self) -> Option<Self::Pointer> {
@@ -311,14 +702,216 @@ impl<T: Display + Debug> Display for CellLinkedList<T> {
     while let Some(n_ptr) = node.clone() {
       node = self.next_node(&n_ptr);
       let curr_val = self.get(&n_ptr).unwrap();
-      let last_val = convert_weak(&n_ptr).unwrap().borrow().prev.clone().map(|t| { 
-        self.get(&Some(t)).unwrap()
-      });
-      vec.push(format!("[{:?} <--- {}]", last_val, curr_val));
+      let last_val = self.prev_node(&n_ptr).map(|t| self.get(&t).unwrap());
+      vec.push(format!("[{:?} <--- {}]", last_val.as_deref(), &*curr_val));
     }
 
     write!(f, "{}", vec.join(" <---> "))
   }
 }
 
-crate::linked_list::macros::dll_tests!(CellLinkedList);
\ No newline at end of file
+// The clone gets nodes of its own, so pointers into this list don't
+// lead anywhere in it.
+impl<T: Clone> Clone for CellLinkedList<T> {
+  fn clone(&self) -> Self {
+    self.iter().map(|elem| T::clone(&elem)).collect()
+  }
+}
+
+impl<T: Debug> Debug for CellLinkedList<T> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    let mut list = f.debug_list();
+    for elem in self.iter() {
+      list.entry(&*elem);
+    }
+    list.finish()
+  }
+}
+
+impl<T: PartialEq> PartialEq for CellLinkedList<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.size == other.size && self.iter().zip(other.iter()).all(|(a, b)| *a == *b)
+  }
+}
+
+impl<T: Eq> Eq for CellLinkedList<T> {}
+
+impl<T: Hash> Hash for CellLinkedList<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write_usize(self.size);
+    for elem in self.iter() {
+      elem.hash(state);
+    }
+  }
+}
+
+impl<T> Default for CellLinkedList<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T> Extend<T> for CellLinkedList<T> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    for elem in iter {
+      self.push_back(elem);
+    }
+  }
+}
+
+impl<T> FromIterator<T> for CellLinkedList<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    let mut list = Self::new();
+    list.extend(iter);
+    list
+  }
+}
+
+crate::linked_list::macros::dll_tests!(CellLinkedList);
+
+#[cfg(test)]
+crate::dll_conformance!(CellLinkedList<u32>);
+
+#[cfg(test)]
+mod invariant_test {
+  use super::*;
+
+  fn list() -> CellLinkedList<i32> {
+    let mut l = CellLinkedList::new();
+    for i in 0..3 {
+      l.push_back(i);
+    }
+    l
+  }
+
+  #[test]
+  fn healthy() {
+    let mut l = list();
+    assert_eq!(l.check_invariants(), Ok(()));
+    let mut rest = l.split_off(&l.tail().unwrap());
+    assert_eq!(rest.check_invariants(), Ok(()));
+    l.append(&mut rest);
+    assert_eq!(l.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn broken_links() {
+    let l = list();
+    let head = l.head.clone().unwrap();
+    let last = l.tail.clone().unwrap();
+    last.borrow_mut().prev = Some(Rc::downgrade(&head));
+    assert_eq!(l.check_invariants(), Err(InvariantError::BrokenBackLink { position: 2 }));
+
+    let mut l = list();
+    l.tail = l.head.clone();
+    assert_eq!(l.check_invariants(), Err(InvariantError::WrongTail));
+
+    let mut l = list();
+    l.size = 2;
+    assert_eq!(l.check_invariants(), Err(InvariantError::SizeMismatch { size: 2, reachable: 3 }));
+  }
+}
+
+#[cfg(test)]
+mod reorder_test {
+  use super::*;
+
+  #[test]
+  fn keeps_pointers() {
+    let mut l = CellLinkedList::new();
+    let ptrs: Vec<_> = [2, 0, 1].into_iter().map(|i| l.push_back(i)).collect();
+
+    l.sort_by(|a, b| a.cmp(b));
+    assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![0, 1, 2]);
+    l.reverse();
+    assert_eq!(l.iter().map(|n| *n).collect::<Vec<_>>(), vec![2, 1, 0]);
+
+    // The nodes themselves never move, only their links.
+    assert_eq!(l.get(&ptrs[0]).as_deref(), Some(&2));
+    let same = |a: &WeakNodePointer<i32>, b: &WeakNodePointer<i32>| {
+      Weak::ptr_eq(a.as_ref().unwrap(), b.as_ref().unwrap())
+    };
+    assert!(same(&l.head().unwrap(), &ptrs[0]));
+    assert!(same(&l.next_node(&ptrs[2]).unwrap(), &ptrs[1]));
+    assert_eq!(l.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn keeps_every_node() {
+    // Relinking in place never drops a node or leaves one behind.
+    let counter = Rc::new(());
+    let mut l = CellLinkedList::new();
+    for i in 0..100 {
+      l.push_back(((i * 37) % 11, Rc::clone(&counter)));
+    }
+    l.sort_by(|a, b| a.0.cmp(&b.0));
+    l.reverse();
+    assert_eq!(Rc::strong_count(&counter), 101);
+    let keys: Vec<_> = l.iter().map(|n| n.0).collect();
+    assert!(keys.windows(2).all(|w| w[0] >= w[1]));
+
+    let weak = l.head().unwrap();
+    drop(l);
+    assert_eq!(Rc::strong_count(&counter), 1);
+    assert!(weak.unwrap().upgrade().is_none());
+  }
+}
+
+#[cfg(test)]
+mod owner_test {
+  use super::*;
+
+  #[test]
+  fn pointers_follow_their_nodes() {
+    let mut a = CellLinkedList::new();
+    let mut b = CellLinkedList::new();
+    let mut c = CellLinkedList::new();
+    let one = a.push_back(1);
+    let two = b.push_back(2);
+    c.push_back(3);
+
+    // The O(1) moves keep pointers working, in whichever list the node
+    // ends up in, and no other.
+    b.prepend(&mut a);
+    c.append(&mut b);
+    assert_eq!(c.get(&one).as_deref(), Some(&1));
+    assert_eq!(c.get(&two).as_deref(), Some(&2));
+    assert!(a.get(&one).is_none() && b.get(&one).is_none());
+
+    // A list that gave its nodes away takes new ones as its own.
+    let four = a.push_back(4);
+    assert_eq!(a.get(&four).as_deref(), Some(&4));
+    assert!(c.get(&four).is_none());
+
+    // split_off hands the nodes it cuts off to the new list.
+    let mut rest = c.split_off(&two);
+    assert_eq!(rest.get(&two).as_deref(), Some(&2));
+    assert!(c.get(&two).is_none());
+    assert_eq!(c.remove(&one), Some(1));
+    assert_eq!(c.iter().map(|n| *n).collect::<Vec<_>>(), vec![3]);
+
+    a.splice_after(&four, &mut rest);
+    assert!(rest.get(&two).is_none());
+    assert_eq!(a.iter().map(|n| *n).collect::<Vec<_>>(), vec![4, 2]);
+    assert_eq!(a.remove(&two), Some(2));
+    assert_eq!(a.check_invariants(), Ok(()));
+  }
+}
+
+#[cfg(test)]
+mod traits_test {
+  use super::*;
+
+  #[test]
+  fn clone_has_its_own_nodes() {
+    let mut l: CellLinkedList<i32> = (1..6).collect();
+
+    // The clone has its own nodes, and old pointers don't reach them.
+    let head = l.head().unwrap();
+    let copy = l.clone();
+    assert_eq!(l, copy);
+    *l.get_mut(&head).unwrap() = 10;
+    assert_eq!(copy.peek_front().as_deref(), Some(&1));
+    assert_ne!(l, copy);
+  }
+}
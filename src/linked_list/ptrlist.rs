@@ -0,0 +1,658 @@
+use crate::linked_list::DLL;
+use crate::map::HashMap;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+
+// Every list gets its own id, so a pointer from one list is turned away
+// by every other list instead of being followed into memory it doesn't
+// own.
+static NEXT_LIST_ID: AtomicU64 = AtomicU64::new(0);
+
+struct BodyNode<T> {
+  // None while the node sits on the free list.
+  elem: Option<T>,
+  // Bumped every time the node is removed, like the Vec list's slots.
+  generation: u32,
+  next: Link<T>,
+  prev: Link<T>,
+}
+
+type Link<T> = Option<NonNull<BodyNode<T>>>;
+
+// A list of individually allocated nodes, linked by raw pointers. There
+// are no refcounts or borrow flags to update, so moving a node is just
+// rewiring four links.
+//
+// Nodes are never freed before the list is. Removed ones go on a free
+// list and are reused by later pushes, and all of them are freed when
+// the list is dropped. That is what keeps the unsafe below sound: any
+// pointer carrying this list's id points at memory this list still
+// owns, so it can always be read, and the generation tells whether the
+// node in it is still the one the pointer was handed out for.
+pub struct PtrLinkedList<T> {
+  head: Link<T>,
+  tail: Link<T>,
+  size: usize,
+  // Removed nodes waiting to be reused, chained through next.
+  free: Link<T>,
+  id: u64,
+  _owns: PhantomData<Box<BodyNode<T>>>,
+}
+
+// The list only hands out access to its elements through &self and
+// &mut self, the same as a Vec would.
+unsafe impl<T: Send> Send for PtrLinkedList<T> {}
+unsafe impl<T: Sync> Sync for PtrLinkedList<T> {}
+
+pub struct NodePointer<T> {
+  node: NonNull<BodyNode<T>>,
+  generation: u32,
+  list: u64,
+}
+
+// A pointer is only ever followed by the list it came from, so it is as
+// safe to send as the elements are.
+unsafe impl<T: Send> Send for NodePointer<T> {}
+unsafe impl<T: Sync> Sync for NodePointer<T> {}
+
+impl<T> Clone for NodePointer<T> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<T> Copy for NodePointer<T> {}
+
+impl<T> PartialEq for NodePointer<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.node == other.node && self.generation == other.generation && self.list == other.list
+  }
+}
+
+impl<T> Eq for NodePointer<T> {}
+
+impl<T> Hash for NodePointer<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.node.hash(state);
+    self.generation.hash(state);
+    self.list.hash(state);
+  }
+}
+
+impl<T> fmt::Debug for NodePointer<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("NodePointer")
+      .field("node", &self.node)
+      .field("generation", &self.generation)
+      .field("list", &self.list)
+      .finish()
+  }
+}
+
+impl<T> PtrLinkedList<T> {
+  // Moves every element of other to the back of this list, and returns
+  // a map from each one's old pointer to its new one.
+  pub fn append_with_remap(&mut self, other: &mut Self) -> HashMap<NodePointer<T>, NodePointer<T>> {
+    let mut remap = HashMap::with_capacity(other.size);
+    self.adopt(other, self.tail, |old, new| {
+      remap.insert(old, new);
+    });
+    remap
+  }
+
+  // Cuts the list in two just before ptr, and returns the part that
+  // starts at ptr along with a map from the old pointers of its
+  // elements to their pointers in the new list.
+  pub fn split_off_with_remap(
+    &mut self,
+    ptr: &NodePointer<T>,
+  ) -> (Self, HashMap<NodePointer<T>, NodePointer<T>>) {
+    let mut remap = HashMap::new();
+    let rest = self.cut(ptr, |old, new| {
+      remap.insert(old, new);
+    });
+    (rest, remap)
+  }
+
+  // Moves every element of other in right after ptr, the same way as
+  // append_with_remap. A stale ptr moves nothing and gives an empty map.
+  pub fn splice_after_with_remap(
+    &mut self,
+    ptr: &NodePointer<T>,
+    other: &mut Self,
+  ) -> HashMap<NodePointer<T>, NodePointer<T>> {
+    let mut remap = HashMap::new();
+    if let Some(node) = self.live(ptr) {
+      remap.reserve(other.size);
+      self.adopt(other, Some(node), |old, new| {
+        remap.insert(old, new);
+      });
+    }
+    remap
+  }
+
+  // Empties other, front to back, into this list right after prev.
+  // moved hears about each element's old and new pointer.
+  fn adopt<F>(&mut self, other: &mut Self, prev: Link<T>, mut moved: F)
+  where F: FnMut(NodePointer<T>, NodePointer<T>)
+  {
+    let mut prev = prev;
+    while let Some(node) = other.head {
+      let old = other.pointer(node);
+      let next = match prev {
+        Some(p) => self.next_of(p),
+        None => self.head,
+      };
+      let new = self.link(other.unlink(node), prev, next);
+      moved(old, new);
+      prev = Some(new.node);
+    }
+  }
+
+  // Moves the elements from ptr to the tail into a list of their own.
+  // moved hears about each element's old and new pointer. A stale ptr
+  // moves nothing.
+  fn cut<F>(&mut self, ptr: &NodePointer<T>, mut moved: F) -> Self
+  where F: FnMut(NodePointer<T>, NodePointer<T>)
+  {
+    let mut rest = Self::new();
+    let mut at = self.live(ptr);
+    while let Some(node) = at {
+      at = self.next_of(node);
+      let old = self.pointer(node);
+      let new = rest.link(self.unlink(node), rest.tail, None);
+      moved(old, new);
+    }
+    rest
+  }
+}
+
+// The unsafe core. Apart from Drop and PtrIterMut, everything else
+// goes through these methods and never touches a raw pointer.
+impl<T> PtrLinkedList<T> {
+  // The node a pointer refers to, if it came from this list and its
+  // node hasn't been removed since.
+  fn live(&self, ptr: &NodePointer<T>) -> Option<NonNull<BodyNode<T>>> {
+    if ptr.list != self.id {
+      return None;
+    }
+
+    // SAFETY: the id matches, so this list allocated the node, and it
+    // can't have been freed while the list is still around.
+    let node = unsafe { ptr.node.as_ref() };
+    if node.generation != ptr.generation || node.elem.is_none() {
+      return None;
+    }
+
+    Some(ptr.node)
+  }
+
+  fn pointer(&self, node: NonNull<BodyNode<T>>) -> NodePointer<T> {
+    // SAFETY: only called with nodes that are linked into this list.
+    let generation = unsafe { (*node.as_ptr()).generation };
+    NodePointer { node, generation, list: self.id }
+  }
+
+  fn next_of(&self, node: NonNull<BodyNode<T>>) -> Link<T> {
+    // SAFETY: as in pointer.
+    unsafe { (*node.as_ptr()).next }
+  }
+
+  fn prev_of(&self, node: NonNull<BodyNode<T>>) -> Link<T> {
+    // SAFETY: as in pointer.
+    unsafe { (*node.as_ptr()).prev }
+  }
+
+  fn elem(&self, node: NonNull<BodyNode<T>>) -> &T {
+    // SAFETY: as in pointer. Handing out &T is fine for as long as self
+    // is borrowed, since changing an element takes &mut self.
+    unsafe { (*node.as_ptr()).elem.as_ref().expect("Removed nodes are never linked") }
+  }
+
+  fn elem_mut(&mut self, node: NonNull<BodyNode<T>>) -> &mut T {
+    // SAFETY: as in elem, and &mut self means nothing else is looking.
+    unsafe { (*node.as_ptr()).elem.as_mut().expect("Removed nodes are never linked") }
+  }
+
+  // Puts elem in a free node if there is one, or a new one otherwise,
+  // and links it in between prev and next.
+  fn link(&mut self, elem: T, prev: Link<T>, next: Link<T>) -> NodePointer<T> {
+    let node = match self.free {
+      // SAFETY: free nodes belong to this list too, and aren't linked
+      // anywhere else, so nothing else can be reading them.
+      Some(node) => unsafe {
+        let n = &mut *node.as_ptr();
+        self.free = n.next;
+        n.elem = Some(elem);
+        node
+      },
+      None => NonNull::from(Box::leak(Box::new(BodyNode {
+        elem: Some(elem),
+        generation: 0,
+        next: None,
+        prev: None,
+      }))),
+    };
+
+    self.attach(node, prev, next);
+    self.size += 1;
+    self.pointer(node)
+  }
+
+  // Takes a linked node out of the list, and its element out of it, and
+  // puts the node on the free list.
+  fn unlink(&mut self, node: NonNull<BodyNode<T>>) -> T {
+    self.detach(node);
+    self.size -= 1;
+
+    // SAFETY: node belongs to this list and, now that it is detached,
+    // nothing links to it.
+    unsafe {
+      let n = &mut *node.as_ptr();
+      n.generation = n.generation.wrapping_add(1);
+      n.next = self.free;
+      self.free = Some(node);
+      n.elem.take().expect("Removed nodes are never linked")
+    }
+  }
+
+  // Wires a node that isn't linked anywhere in between prev and next.
+  fn attach(&mut self, node: NonNull<BodyNode<T>>, prev: Link<T>, next: Link<T>) {
+    // SAFETY: all three belong to this list. Only links are written,
+    // never elements, so any &T handed out stays valid.
+    unsafe {
+      (*node.as_ptr()).prev = prev;
+      (*node.as_ptr()).next = next;
+      match prev {
+        Some(p) => (*p.as_ptr()).next = Some(node),
+        None => self.head = Some(node),
+      }
+      match next {
+        Some(n) => (*n.as_ptr()).prev = Some(node),
+        None => self.tail = Some(node),
+      }
+    }
+  }
+
+  // Joins a linked node's neighbours to each other. The node keeps its
+  // element and generation, so pointers to it stay good.
+  fn detach(&mut self, node: NonNull<BodyNode<T>>) {
+    let (prev, next) = (self.prev_of(node), self.next_of(node));
+    // SAFETY: as in attach.
+    unsafe {
+      match prev {
+        Some(p) => (*p.as_ptr()).next = next,
+        None => self.head = next,
+      }
+      match next {
+        Some(n) => (*n.as_ptr()).prev = prev,
+        None => self.tail = prev,
+      }
+    }
+  }
+}
+
+impl<T> Drop for PtrLinkedList<T> {
+  fn drop(&mut self) {
+    for mut link in [self.head.take(), self.free.take()] {
+      while let Some(node) = link {
+        // SAFETY: every node was made by Box::leak in link, is on
+        // exactly one of the two chains, and is freed exactly once here.
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        link = node.next;
+      }
+    }
+  }
+}
+
+// Yields each element once. It holds the list's &mut, so the nodes
+// can't change under it, and each &mut it hands out is to a different
+// node.
+pub struct PtrIterMut<'a, T> {
+  front: Link<T>,
+  back: Link<T>,
+  remaining: usize,
+  _list: PhantomData<&'a mut PtrLinkedList<T>>,
+}
+
+impl<'a, T> PtrIterMut<'a, T> {
+  fn yield_mut(&mut self, node: NonNull<BodyNode<T>>) -> &'a mut T {
+    self.remaining -= 1;
+    // SAFETY: node is linked into the list borrowed for 'a, and
+    // remaining stops front and back from handing it out twice. Only
+    // the elem field is borrowed, so reading the links of this node
+    // later doesn't step on it.
+    unsafe { (*node.as_ptr()).elem.as_mut().expect("Removed nodes are never linked") }
+  }
+}
+
+impl<'a, T> Iterator for PtrIterMut<'a, T> {
+  type Item = &'a mut T;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    let node = self.front?;
+    // SAFETY: as in yield_mut.
+    self.front = unsafe { (*node.as_ptr()).next };
+    Some(self.yield_mut(node))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<T> DoubleEndedIterator for PtrIterMut<'_, T> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    let node = self.back?;
+    // SAFETY: as in yield_mut.
+    self.back = unsafe { (*node.as_ptr()).prev };
+    Some(self.yield_mut(node))
+  }
+}
+
+impl<T> ExactSizeIterator for PtrIterMut<'_, T> {}
+impl<T> FusedIterator for PtrIterMut<'_, T> {}
+
+impl<T> DLL<T> for PtrLinkedList<T> {
+  type Pointer = NodePointer<T>;
+  type Ref<'a> = &'a T where T: 'a;
+  type RefMut<'a> = &'a mut T where T: 'a;
+  type IterMut<'a> = PtrIterMut<'a, T> where T: 'a;
+
+  fn new() -> Self {
+    PtrLinkedList {
+      head: None,
+      tail: None,
+      size: 0,
+      free: None,
+      id: NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed),
+      _owns: PhantomData,
+    }
+  }
+
+  fn size(&self) -> usize {
+    self.size
+  }
+
+  fn peek_front(&self) -> Option<&T> {
+    self.head.map(|node| self.elem(node))
+  }
+
+  fn peek_back(&self) -> Option<&T> {
+    self.tail.map(|node| self.elem(node))
+  }
+
+  fn pop_front(&mut self) -> Option<T> {
+    self.head.map(|node| self.unlink(node))
+  }
+
+  fn pop_back(&mut self) -> Option<T> {
+    self.tail.map(|node| self.unlink(node))
+  }
+
+  fn head(&self) -> Option<NodePointer<T>> {
+    self.head.map(|node| self.pointer(node))
+  }
+
+  fn tail(&self) -> Option<NodePointer<T>> {
+    self.tail.map(|node| self.pointer(node))
+  }
+
+  fn get(&self, ptr: &NodePointer<T>) -> Option<&T> {
+    self.live(ptr).map(|node| self.elem(node))
+  }
+
+  fn get_mut(&mut self, ptr: &NodePointer<T>) -> Option<&mut T> {
+    let node = self.live(ptr)?;
+    Some(self.elem_mut(node))
+  }
+
+  fn replace_val(&mut self, ptr: &NodePointer<T>, elem: T) {
+    if let Some(val) = self.get_mut(ptr) {
+      *val = elem;
+    }
+  }
+
+  fn remove(&mut self, ptr: &NodePointer<T>) -> Option<T> {
+    let node = self.live(ptr)?;
+    Some(self.unlink(node))
+  }
+
+  fn insert_before(&mut self, ptr: &NodePointer<T>, elem: T) -> Option<NodePointer<T>> {
+    let node = self.live(ptr)?;
+    let prev = self.prev_of(node);
+    Some(self.link(elem, prev, Some(node)))
+  }
+
+  fn insert_after(&mut self, ptr: &NodePointer<T>, elem: T) -> Option<NodePointer<T>> {
+    let node = self.live(ptr)?;
+    let next = self.next_of(node);
+    Some(self.link(elem, Some(node), next))
+  }
+
+  // Each list frees its own nodes, so nodes can't change hands, and the
+  // elements move over into nodes of this list instead. That is
+  // O(other), and pointers into other change; append_with_remap says
+  // what to.
+  fn append(&mut self, other: &mut Self) {
+    self.adopt(other, self.tail, |_, _| {});
+  }
+
+  // O(moved nodes), for the same reason as append.
+  fn split_off(&mut self, ptr: &NodePointer<T>) -> Self {
+    self.cut(ptr, |_, _| {})
+  }
+
+  // O(other), see append.
+  fn splice_after(&mut self, ptr: &NodePointer<T>, other: &mut Self) {
+    if let Some(node) = self.live(ptr) {
+      self.adopt(other, Some(node), |_, _| {});
+    }
+  }
+
+  fn prepend(&mut self, other: &mut Self) {
+    self.adopt(other, None, |_, _| {});
+  }
+
+  fn push_back(&mut self, elem: T) -> NodePointer<T> {
+    self.link(elem, self.tail, None)
+  }
+
+  fn push_front(&mut self, elem: T) -> NodePointer<T> {
+    self.link(elem, None, self.head)
+  }
+
+  // Only the links move, so the pointer stays as it was. A stale
+  // pointer is left alone, and so is the list.
+  fn move_back(&mut self, ptr: &mut NodePointer<T>) {
+    if let Some(node) = self.live(ptr) {
+      self.detach(node);
+      self.attach(node, self.tail, None);
+    }
+  }
+
+  fn move_front(&mut self, ptr: &mut NodePointer<T>) {
+    if let Some(node) = self.live(ptr) {
+      self.detach(node);
+      self.attach(node, None, self.head);
+    }
+  }
+
+  fn next_node(&self, ptr: &NodePointer<T>) -> Option<NodePointer<T>> {
+    let node = self.live(ptr)?;
+    self.next_of(node).map(|next| self.pointer(next))
+  }
+
+  fn prev_node(&self, ptr: &NodePointer<T>) -> Option<NodePointer<T>> {
+    let node = self.live(ptr)?;
+    self.prev_of(node).map(|prev| self.pointer(prev))
+  }
+
+  fn iter_mut(&mut self) -> PtrIterMut<'_, T> {
+    PtrIterMut {
+      front: self.head,
+      back: self.tail,
+      remaining: self.size,
+      _list: PhantomData,
+    }
+  }
+}
+
+impl<T: fmt::Display> fmt::Display for PtrLinkedList<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let vec: Vec<String> = self.iter().map(|elem| format!("[{elem}]")).collect();
+    write!(f, "{}", vec.join(" <-> "))
+  }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PtrLinkedList<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+impl<T: PartialEq> PartialEq for PtrLinkedList<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.size == other.size && self.iter().eq(other.iter())
+  }
+}
+
+impl<T: Eq> Eq for PtrLinkedList<T> {}
+
+impl<T: Hash> Hash for PtrLinkedList<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write_usize(self.size);
+    for elem in self.iter() {
+      elem.hash(state);
+    }
+  }
+}
+
+impl<T> Default for PtrLinkedList<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T> Extend<T> for PtrLinkedList<T> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    for elem in iter {
+      self.push_back(elem);
+    }
+  }
+}
+
+impl<T> FromIterator<T> for PtrLinkedList<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    let mut list = Self::new();
+    list.extend(iter);
+    list
+  }
+}
+
+impl<T> IntoIterator for PtrLinkedList<T> {
+  type Item = T;
+  type IntoIter = super::DLLIntoIter<T, Self>;
+  fn into_iter(self) -> Self::IntoIter {
+    super::DLLIntoIter {
+      _wokka: PhantomData,
+      list: self,
+    }
+  }
+}
+
+crate::linked_list::macros::dll_tests!(PtrLinkedList);
+
+#[cfg(test)]
+crate::dll_conformance!(PtrLinkedList<u32>);
+
+
+#[cfg(test)]
+mod ownership_test {
+  use super::*;
+  use std::rc::Rc;
+
+  #[test]
+  fn foreign_and_stale_pointers() {
+    let mut a = PtrLinkedList::new();
+    let mut b = PtrLinkedList::new();
+    let from_a = a.push_back(1);
+    b.push_back(2);
+
+    // b never follows a's pointers, even into a node it also has.
+    assert_eq!(b.get(&from_a), None);
+    assert_eq!(b.remove(&from_a), None);
+    assert!(b.next_node(&from_a).is_none());
+
+    // A removed node is reused, but the old pointer doesn't reach it.
+    assert_eq!(a.remove(&from_a), Some(1));
+    let reused = a.push_back(3);
+    assert_eq!(reused.node, from_a.node);
+    assert_eq!(a.get(&from_a), None);
+    a.replace_val(&from_a, 100);
+    assert_eq!(a.get(&reused), Some(&3));
+
+    // A dropped list's pointers are just turned away.
+    drop(a);
+    assert_eq!(b.get(&reused), None);
+  }
+
+  #[test]
+  fn moves_hand_back_remaps() {
+    let mut a = PtrLinkedList::new();
+    let mut b = PtrLinkedList::new();
+    let one = a.push_back(1);
+    let theirs = [b.push_back(2), b.push_back(3)];
+
+    let remap = a.append_with_remap(&mut b);
+    assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(a.get(&remap[&theirs[0]]), Some(&2));
+    assert_eq!(b.get(&theirs[0]), None);
+    assert_eq!(a.get(&theirs[0]), None);
+
+    let two = remap[&theirs[0]];
+    let (mut rest, remap) = a.split_off_with_remap(&two);
+    assert_eq!(rest.get(&remap[&two]), Some(&2));
+    assert_eq!(a.get(&two), None);
+
+    let remap = a.splice_after_with_remap(&one, &mut rest);
+    assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(remap.len(), 2);
+    assert_eq!(rest.size(), 0);
+  }
+
+  #[test]
+  fn drops_every_elem() {
+    let counter = Rc::new(());
+    let mut l = PtrLinkedList::new();
+    for _ in 0..10 {
+      l.push_back(Rc::clone(&counter));
+    }
+    let ptr = l.head().unwrap();
+    l.remove(&ptr);
+    l.pop_back();
+    assert_eq!(Rc::strong_count(&counter), 9);
+
+    let rest = l.split_off(&l.head().unwrap());
+    assert_eq!(Rc::strong_count(&counter), 9);
+    drop(l);
+    drop(rest);
+    assert_eq!(Rc::strong_count(&counter), 1);
+  }
+}
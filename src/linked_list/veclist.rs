@@ -1,128 +1,464 @@
-use std::mem;
-use std::fmt;
-use crate::linked_list::DLL;
-use std::marker::PhantomData;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::mem;
+use crate::linked_list::{DLL, InvariantError, debug_validate, next_list_id};
+use crate::map::HashMap;
+use core::marker::PhantomData;
 
 
-struct BodyNode<T> {
+// What nodes use to point at each other: an index into the spine. A
+// smaller index type makes every node smaller, but caps how many nodes
+// the list can hold. The largest value is kept back to mean "no node",
+// which is what the first node's prev and the last node's next are.
+pub trait SpineIndex: Copy + Eq + Hash + fmt::Debug {
+  const NONE: Self;
+
+  // Panics if i doesn't fit, which means the list is full.
+  fn from_usize(i: usize) -> Self;
+  fn to_usize(self) -> usize;
+}
+
+macro_rules! spine_index {
+  ($($t:ty),*) => {$(
+    impl SpineIndex for $t {
+      const NONE: Self = <$t>::MAX;
+
+      fn from_usize(i: usize) -> Self {
+        match <$t>::try_from(i) {
+          Ok(i) if i != Self::NONE => i,
+          _ => panic!("A {} spine can't hold more than {} nodes", stringify!($t), Self::NONE),
+        }
+      }
+
+      fn to_usize(self) -> usize {
+        self as usize
+      }
+    }
+  )*}
+}
+
+spine_index!(u16, u32, usize);
+
+#[derive(Clone)]
+struct BodyNode<T, I> {
   elem: T,
-  next: NodePointer,
-  prev: NodePointer,
+  next: I,
+  prev: I,
 }
 
-struct HeadNode { next: NodePointer }
-struct TailNode { prev: NodePointer }
+// Slots are reused once freed, so each one counts how many times that
+// has happened. A pointer remembers the generation it was handed out
+// in, and stops working as soon as its node is removed, even if another
+// node moves into the same slot later. Moving a node around the list
+// keeps it in its slot, so only removing one uses up a generation. A
+// slot that runs out of generations is retired instead of wrapping
+// round, so an old pointer can't come back that way.
+#[derive(Clone)]
+struct Slot<T, I> {
+  generation: u32,
+  node: Option<BodyNode<T, I>>,
+}
 
-pub struct VectorLinkedList<T> {
-  spine: Vec<Option<BodyNode<T>>>,
+// Every list has an id, and pointers carry the id of the list that
+// handed them out, so a pointer from another list is stale here even
+// when its index and generation happen to match a node.
+pub struct IndexedLinkedList<T, I: SpineIndex> {
+  spine: Vec<Slot<T, I>>,
   size: usize,
-  head: HeadNode,
-  tail: TailNode,
-  free_list: Vec<usize>,
+  // First and last nodes, NONE when the list is empty.
+  head: I,
+  tail: I,
+  free_list: Vec<I>,
+  // The generation new slots start at. Compacting raises it past every
+  // generation handed out so far, so a slot that is dropped and pushed
+  // again doesn't bring old pointers back to life.
+  generation_floor: u32,
+  id: u32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum NodePointer {
-  Head,
-  Tail,
-  Body(usize),
+// The clone copies the spine as is, but gets an id of its own, so
+// pointers into this list don't lead anywhere in it.
+impl<T: Clone, I: SpineIndex> Clone for IndexedLinkedList<T, I> {
+  fn clone(&self) -> Self {
+    Self {
+      spine: self.spine.clone(),
+      size: self.size,
+      head: self.head,
+      tail: self.tail,
+      free_list: self.free_list.clone(),
+      generation_floor: self.generation_floor,
+      id: next_list_id(),
+    }
+  }
 }
 
-impl<T> VectorLinkedList<T> {
-  fn insert_between(&mut self, elem: T, p: &NodePointer, n: &NodePointer) -> NodePointer {
-    let new_node = BodyNode {
-      elem: elem, next: n.clone(), prev: p.clone(),
-    };
+// The original layout, as many nodes as memory allows.
+pub type VectorLinkedList<T> = IndexedLinkedList<T, usize>;
+// Half the link overhead of VectorLinkedList on 64 bit targets, for up
+// to u32::MAX - 1 nodes.
+pub type CompactLinkedList<T> = IndexedLinkedList<T, u32>;
+// For lists that stay under 65535 nodes.
+pub type SmallLinkedList<T> = IndexedLinkedList<T, u16>;
 
-    // If our insert node is within the bounds of the array
-    let free_space = self.free_list.pop();
-    let insert_at;
+// Slot index and the generation of that slot, and the id of the list
+// that handed it out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodePointer<I = usize> {
+  index: I,
+  generation: u32,
+  list: u32,
+}
 
-    if let Some(i) = free_space {
-      self.spine[i] = Some(new_node);
-      insert_at = i;
-    } else {
-      self.spine.push(Some(new_node));
-      insert_at = self.spine.len() - 1;
+impl<T, I: SpineIndex> IndexedLinkedList<T, I> {
+  pub fn with_capacity(capacity: usize) -> Self {
+    let mut list = Self::new();
+    list.spine.reserve_exact(capacity);
+    list
+  }
+
+  // Makes room for at least additional more nodes without growing the
+  // spine again. Free slots count towards it.
+  pub fn reserve(&mut self, additional: usize) {
+    self.spine.reserve(additional.saturating_sub(self.free_list.len()));
+  }
+
+  // How many nodes fit before the spine has to grow.
+  pub fn capacity(&self) -> usize {
+    self.spine.capacity() - self.spine.len() + self.free_list.len()
+  }
+
+  // Moves every node into the front of the spine, in list order, and
+  // drops the free slots behind them. Every pointer into the list
+  // changes, so the returned map takes each old pointer to its new one.
+  // Pointers that aren't in the map are stale, and stay that way.
+  pub fn compact(&mut self) -> HashMap<NodePointer<I>, NodePointer<I>> {
+    let generation = self.next_generation();
+
+    let mut old = mem::take(&mut self.spine);
+    self.free_list.clear();
+    self.generation_floor = generation;
+
+    let mut remap = HashMap::with_capacity(self.size);
+    let mut prev = I::NONE;
+    let mut curr = mem::replace(&mut self.head, I::NONE);
+
+    while curr != I::NONE {
+      let slot = &mut old[curr.to_usize()];
+      let node = slot.node.take().expect("a link leads to an empty slot");
+      let at = I::from_usize(self.spine.len());
+      remap.insert(
+        NodePointer { index: curr, generation: slot.generation, list: self.id },
+        NodePointer { index: at, generation, list: self.id },
+      );
+
+      self.spine.push(Slot {
+        generation,
+        node: Some(BodyNode { elem: node.elem, next: I::NONE, prev }),
+      });
+      self.set_next(prev, at);
+      prev = at;
+      curr = node.next;
     }
 
-    match *n {
-      NodePointer::Head => panic!("Head cannot be referred to by next"),
-      NodePointer::Tail => self.tail.prev = NodePointer::Body(insert_at),
-      NodePointer::Body(next) => {
-        match &mut self.spine[next] {
-          None => panic!("I hate this"),
-          Some(node) => node.prev = NodePointer::Body(insert_at),
-        }
-      }
+    self.tail = prev;
+    debug_validate(self.size, || self.check_invariants());
+    remap
+  }
+
+  // Moves every node of other to the back of this list, and returns a
+  // map from each moved node's old pointer to its new one. The nodes go
+  // straight into this spine, in free slots first and then new ones, so
+  // it is one pass over other and the spine grows at most once. Pointers
+  // into other are stale afterwards, and stay that way.
+  pub fn append_with_remap(&mut self, other: &mut Self) -> HashMap<NodePointer<I>, NodePointer<I>> {
+    let mut remap = HashMap::with_capacity(other.size);
+    self.adopt(other, self.tail, I::NONE, |old, new| {
+      remap.insert(old, new);
+    });
+    remap
+  }
+
+  // Cuts the list in two just before n, and returns the part that
+  // starts at n along with a map from the old pointers of its nodes to
+  // their pointers in the new list. One pass over the moved nodes; their
+  // slots here are freed, and the new list gets a spine of just them.
+  pub fn split_off_with_remap(
+    &mut self,
+    n: &NodePointer<I>,
+  ) -> (Self, HashMap<NodePointer<I>, NodePointer<I>>) {
+    let mut remap = HashMap::new();
+    let rest = self.cut(n, |old, new| {
+      remap.insert(old, new);
+    });
+    (rest, remap)
+  }
+
+  // Moves every node of other in right after n, the same way as
+  // append_with_remap. A stale n moves nothing and gives an empty map.
+  pub fn splice_after_with_remap(
+    &mut self,
+    n: &NodePointer<I>,
+    other: &mut Self,
+  ) -> HashMap<NodePointer<I>, NodePointer<I>> {
+    let mut remap = HashMap::new();
+    if let Some(node) = self.node(n) {
+      let next = node.next;
+      remap.reserve(other.size);
+      self.adopt(other, n.index, next, |old, new| {
+        remap.insert(old, new);
+      });
     }
+    remap
+  }
 
-    match *p {
-      NodePointer::Tail => panic!("Tail cannot be referred to by prev"),
-      NodePointer::Head => self.head.next = NodePointer::Body(insert_at),
-      NodePointer::Body(prev) => {
-        match &mut self.spine[prev] {
-          None => panic!("I hate this"),
-          Some(node) => node.next = NodePointer::Body(insert_at),
-        }
-      }
+  // Compacts, then gives back whatever memory the spine and free list
+  // no longer need. Returns the same map as compact.
+  pub fn shrink_to_fit(&mut self) -> HashMap<NodePointer<I>, NodePointer<I>> {
+    let remap = self.compact();
+    self.spine.shrink_to_fit();
+    self.free_list.shrink_to_fit();
+    remap
+  }
+
+  // A generation newer than any slot has used, so starting slots at it
+  // can't bring an old pointer back to life. It stops at u32::MAX rather
+  // than wrapping; a list that gets there has slots that were each
+  // reused billions of times, and from then on compacting it no longer
+  // guarantees old pointers stay stale.
+  fn next_generation(&self) -> u32 {
+    self.spine.iter()
+      .map(|slot| slot.generation.saturating_add(1))
+      .fold(self.generation_floor, u32::max)
+  }
+
+  // Empties slot i, which has to hold a node, and hands the node back.
+  // Bumping the generation makes every pointer to it stale. u32::MAX is
+  // never handed out: a slot that gets there is out of generations, and
+  // is left empty and off the free list for good.
+  fn free(&mut self, i: I) -> BodyNode<T, I> {
+    let slot = &mut self.spine[i.to_usize()];
+    let node = slot.node.take().expect("a link leads to an empty slot");
+    slot.generation = slot.generation.saturating_add(1);
+    if slot.generation != u32::MAX {
+      self.free_list.push(i);
     }
+    node
+  }
 
-    self.size += 1;
-    NodePointer::Body(insert_at)
+  fn retired(slot: &Slot<T, I>) -> bool {
+    slot.node.is_none() && slot.generation == u32::MAX
   }
 
-  fn remove(&mut self, n: &NodePointer) -> Option<T> {
-    let vec_index = match *n {
-      NodePointer::Body(i) => i,
-      _ => return None
+  // Puts a node in a free slot if there is one, otherwise in a new one
+  // at the end of the spine. Links are left to the caller.
+  fn place(&mut self, node: BodyNode<T, I>) -> I {
+    match self.free_list.pop() {
+      Some(i) => {
+        self.spine[i.to_usize()].node = Some(node);
+        i
+      },
+      None => {
+        let i = I::from_usize(self.spine.len());
+        self.spine.push(Slot { generation: self.generation_floor, node: Some(node) });
+        i
+      },
+    }
+  }
+
+  // Moves every node of other, in order, into this spine in between the
+  // nodes at prev and next, which have to be neighbours. moved hears
+  // about each node's old and new pointer. other is left empty.
+  fn adopt<F>(&mut self, other: &mut Self, prev: I, next: I, mut moved: F)
+  where F: FnMut(NodePointer<I>, NodePointer<I>)
+  {
+    self.reserve(other.size);
+    let mut prev = prev;
+    let mut curr = other.head;
+    while curr != I::NONE {
+      let slot = &mut other.spine[curr.to_usize()];
+      let node = slot.node.take().expect("a link leads to an empty slot");
+      let old = NodePointer { index: curr, generation: slot.generation, list: other.id };
+      curr = node.next;
+
+      let at = self.place(BodyNode { elem: node.elem, next: I::NONE, prev });
+      self.set_next(prev, at);
+      moved(old, NodePointer { index: at, generation: self.spine[at.to_usize()].generation, list: self.id });
+      prev = at;
+    }
+    self.set_next(prev, next);
+    self.set_prev(next, prev);
+
+    self.size += other.size;
+    other.clear();
+    debug_validate(self.size, || self.check_invariants());
+  }
+
+  // Takes the nodes from n to the tail out into a list of their own.
+  // moved hears about each node's old and new pointer. A stale n moves
+  // nothing.
+  fn cut<F>(&mut self, n: &NodePointer<I>, mut moved: F) -> Self
+  where F: FnMut(NodePointer<I>, NodePointer<I>)
+  {
+    let mut rest = Self::new();
+    let last = match self.node(n) {
+      Some(node) => node.prev,
+      None => return rest,
     };
 
-    let existing_node = match mem::replace(&mut self.spine[vec_index], None) {
-      None => return None,
+    let mut curr = n.index;
+    while curr != I::NONE {
+      let old = NodePointer { index: curr, generation: self.spine[curr.to_usize()].generation, list: self.id };
+      let node = self.free(curr);
+      curr = node.next;
+
+      let at = I::from_usize(rest.spine.len());
+      let prev = rest.tail;
+      rest.spine.push(Slot { generation: 0, node: Some(BodyNode { elem: node.elem, next: I::NONE, prev }) });
+      rest.set_next(prev, at);
+      rest.tail = at;
+      moved(old, NodePointer { index: at, generation: 0, list: rest.id });
+    }
+
+    rest.size = rest.spine.len();
+    self.size -= rest.size;
+    self.set_next(last, I::NONE);
+    self.tail = last;
+    debug_validate(self.size, || self.check_invariants());
+    debug_validate(rest.size, || rest.check_invariants());
+    rest
+  }
+
+  // Drops every node. The spine is emptied rather than freed slot by
+  // slot, so new slots start past every generation handed out so far.
+  fn clear(&mut self) {
+    self.generation_floor = self.next_generation();
+    self.spine.clear();
+    self.free_list.clear();
+    self.head = I::NONE;
+    self.tail = I::NONE;
+    self.size = 0;
+  }
+
+  // The node a pointer refers to, if it came from this list and is
+  // still the one that was handed out for it.
+  fn node(&self, n: &NodePointer<I>) -> Option<&BodyNode<T, I>> {
+    if n.list != self.id {
+      return None;
+    }
+    let slot = self.spine.get(n.index.to_usize())?;
+    if slot.generation != n.generation {
+      return None;
+    }
+    slot.node.as_ref()
+  }
+
+  fn node_mut(&mut self, n: &NodePointer<I>) -> Option<&mut BodyNode<T, I>> {
+    if n.list != self.id {
+      return None;
+    }
+    let slot = self.spine.get_mut(n.index.to_usize())?;
+    if slot.generation != n.generation {
+      return None;
+    }
+    slot.node.as_mut()
+  }
+
+  // Links between nodes always lead to a live one, so these don't need
+  // a generation to check.
+  fn node_at(&mut self, i: I) -> &mut BodyNode<T, I> {
+    match &mut self.spine[i.to_usize()].node {
+      None => panic!("a link leads to an empty slot"),
       Some(node) => node,
-    };
+    }
+  }
 
-    // Free up space in the vector array
-    self.free_list.push(vec_index);
-    self.size -= 1;
+  fn pointer_at(&self, i: I) -> Option<NodePointer<I>> {
+    if i == I::NONE {
+      return None;
+    }
+    Some(NodePointer { index: i, generation: self.spine[i.to_usize()].generation, list: self.id })
+  }
 
-    match existing_node.next {
-      NodePointer::Head => panic!("Head cannot be referred to by next"),
-      NodePointer::Tail => self.tail.prev = existing_node.prev,
-      NodePointer::Body(next) => {
-        match &mut self.spine[next] {
-          None => panic!("I hate this"),
-          Some(node) => node.prev = existing_node.prev,
-        }
-      }
+  // Points the node at i forwards to next. NONE stands in for the head.
+  fn set_next(&mut self, i: I, next: I) {
+    if i == I::NONE {
+      self.head = next;
+    } else {
+      self.node_at(i).next = next;
     }
+  }
 
-    match existing_node.prev {
-      NodePointer::Tail => panic!("Tail cannot be referred to by prev"),
-      NodePointer::Head => self.head.next = existing_node.next,
-      NodePointer::Body(prev) => {
-        match &mut self.spine[prev] {
-          None => panic!("I hate this"),
-          Some(node) => node.next = existing_node.next,
-        }
-      }
+  // Points the node at i back to prev. NONE stands in for the tail.
+  fn set_prev(&mut self, i: I, prev: I) {
+    if i == I::NONE {
+      self.tail = prev;
+    } else {
+      self.node_at(i).prev = prev;
     }
+  }
 
-    return Some(existing_node.elem);
+  fn elem_at(&self, i: I) -> &T {
+    &self.spine[i.to_usize()].node.as_ref().expect("a link leads to an empty slot").elem
+  }
+
+  fn next_at(&self, i: I) -> I {
+    self.spine[i.to_usize()].node.as_ref().expect("a link leads to an empty slot").next
+  }
+
+  // Takes the node at i out of the links, leaving it in its slot.
+  fn unlink(&mut self, i: I) {
+    let node = self.node_at(i);
+    let (prev, next) = (node.prev, node.next);
+    self.set_prev(next, prev);
+    self.set_next(prev, next);
+  }
+
+  // Links the node at i, which is in no list, in between p and n.
+  fn link_between(&mut self, i: I, p: I, n: I) {
+    let node = self.node_at(i);
+    node.prev = p;
+    node.next = n;
+    self.set_prev(n, i);
+    self.set_next(p, i);
+  }
+
+  fn insert_between(&mut self, elem: T, p: I, n: I) -> NodePointer<I> {
+    let new_node = BodyNode {
+      elem, next: n, prev: p,
+    };
+
+    // Reuse a free slot if there is one, otherwise grow the spine.
+    let insert_at = self.place(new_node);
+
+    self.set_prev(n, insert_at);
+    self.set_next(p, insert_at);
+
+    self.size += 1;
+    debug_validate(self.size, || self.check_invariants());
+    NodePointer { index: insert_at, generation: self.spine[insert_at.to_usize()].generation, list: self.id }
   }
 }
 
-impl<T> DLL<T> for VectorLinkedList<T> {
-  type Pointer = NodePointer;
+impl<T, I: SpineIndex> DLL<T> for IndexedLinkedList<T, I> {
+  type Pointer = NodePointer<I>;
+  type Ref<'a> = &'a T where T: 'a, I: 'a;
+  type RefMut<'a> = &'a mut T where T: 'a, I: 'a;
+  type IterMut<'a> = VecIterMut<'a, T, I> where T: 'a, I: 'a;
 
   fn new() -> Self {
     Self {
       spine: Vec::new(),
       size: 0,
-      head: HeadNode { next: NodePointer::Tail },
-      tail: TailNode { prev: NodePointer::Head },
+      head: I::NONE,
+      tail: I::NONE,
       free_list: Vec::new(),
+      generation_floor: 0,
+      id: next_list_id(),
     }
   }
 
@@ -130,122 +466,350 @@ impl<T> DLL<T> for VectorLinkedList<T> {
     self.size
   }
 
-  fn get(&self, n: &NodePointer) -> Option<&T> {
-    match *n {
-      NodePointer::Body(i) => self.spine[i].as_ref().map(|node| &node.elem),
-      _ => None
+  fn reserve(&mut self, additional: usize) {
+    IndexedLinkedList::reserve(self, additional);
+  }
+
+  // Free slots, plus however many more the spine can index before
+  // reaching NONE. Only the smaller index types get anywhere near that.
+  fn has_room(&self, additional: usize) -> bool {
+    additional <= self.free_list.len() + (I::NONE.to_usize() - self.spine.len())
+  }
+
+  fn get(&self, n: &NodePointer<I>) -> Option<&T> {
+    self.node(n).map(|node| &node.elem)
+  }
+
+  fn get_mut(&mut self, n: &NodePointer<I>) -> Option<&mut T> {
+    self.node_mut(n).map(|node| &mut node.elem)
+  }
+
+  fn replace_val(&mut self, n: &NodePointer<I>, elem: T) {
+    if let Some(node) = self.node_mut(n) {
+      node.elem = elem;
     }
   }
 
-  fn get_mut(&mut self, n: &NodePointer) -> Option<&mut T> {
-    match *n {
-      NodePointer::Body(i) => self.spine[i].as_mut().map(|node| &mut node.elem),
-      _ => None
+  fn remove(&mut self, n: &NodePointer<I>) -> Option<T> {
+    // Stale pointers remove nothing.
+    self.node(n)?;
+
+    // Free up space in the vector array.
+    let existing_node = self.free(n.index);
+    self.size -= 1;
+
+    self.set_prev(existing_node.next, existing_node.prev);
+    self.set_next(existing_node.prev, existing_node.next);
+
+    debug_validate(self.size, || self.check_invariants());
+    Some(existing_node.elem)
+  }
+
+  fn insert_before(&mut self, n: &NodePointer<I>, elem: T) -> Option<NodePointer<I>> {
+    let prev = self.node(n)?.prev;
+    Some(self.insert_between(elem, prev, n.index))
+  }
+
+  fn insert_after(&mut self, n: &NodePointer<I>, elem: T) -> Option<NodePointer<I>> {
+    let next = self.node(n)?.next;
+    Some(self.insert_between(elem, n.index, next))
+  }
+
+  // Nodes move into this spine in one pass, so this is O(other), and
+  // the pointers into other change. append_with_remap says what to.
+  fn append(&mut self, other: &mut Self) {
+    self.adopt(other, self.tail, I::NONE, |_, _| {});
+  }
+
+  // O(moved nodes), and their pointers change; see split_off_with_remap.
+  fn split_off(&mut self, n: &NodePointer<I>) -> Self {
+    self.cut(n, |_, _| {})
+  }
+
+  // O(other), see append and splice_after_with_remap.
+  fn splice_after(&mut self, n: &NodePointer<I>, other: &mut Self) {
+    if let Some(node) = self.node(n) {
+      let next = node.next;
+      self.adopt(other, n.index, next, |_, _| {});
     }
   }
 
-  fn replace_val(&mut self, n: &NodePointer, elem: T) {
-    match *n {
-      NodePointer::Body(i) => {
-        match &self.spine[i] {
-          Some(curr_node) => {
-            self.spine[i] = Some(
-              BodyNode {
-                elem: elem,
-                ..*curr_node
-              }
-            );
-            Some(*n)
-          },
-          None => None,
-        }
-      },
-      _ => None
-    };
+  // O(other), the same way as append.
+  fn prepend(&mut self, other: &mut Self) {
+    self.adopt(other, I::NONE, self.head, |_, _| {});
   }
 
-  fn push_back(&mut self, elem: T) -> NodePointer {
-    return self.insert_between(elem, &self.tail.prev.clone(), &NodePointer::Tail);
+  fn push_back(&mut self, elem: T) -> NodePointer<I> {
+    self.insert_between(elem, self.tail, I::NONE)
   }
 
-  fn push_front(& mut self, elem: T) -> NodePointer {
-    return self.insert_between(elem, &NodePointer::Head, &self.head.next.clone());
+  fn push_front(& mut self, elem: T) -> NodePointer<I> {
+    self.insert_between(elem, I::NONE, self.head)
   }
 
   fn pop_front(&mut self) -> Option<T> {
-    self.remove(&self.head.next.clone())
+    let head = self.head()?;
+    self.remove(&head)
   }
 
   fn pop_back(&mut self) -> Option<T> {
-    self.remove(&self.tail.prev.clone())
+    let tail = self.tail()?;
+    self.remove(&tail)
   }
 
   fn peek_front(&self) -> Option<&T> {
-    self.get(&self.head.next)
+    self.get(&self.head()?)
   }
 
   fn peek_back(&self) -> Option<&T> {
-    self.get(&self.tail.prev)
+    self.get(&self.tail()?)
   }
 
-  fn move_back(&mut self, n: &mut NodePointer) {
-    let mut new_ptr = self.remove(n).map(|elem| self.push_back(elem)).unwrap();
-    mem::swap(n, &mut new_ptr);
+  // The node is relinked where it is, so the pointer stays the same and
+  // the slot keeps its generation. A stale pointer is left alone, and so
+  // is the list.
+  fn move_back(&mut self, n: &mut NodePointer<I>) {
+    if self.node(n).is_none() || n.index == self.tail {
+      return;
+    }
+    self.unlink(n.index);
+    self.link_between(n.index, self.tail, I::NONE);
+    debug_validate(self.size, || self.check_invariants());
   }
 
-  fn move_front(&mut self, n: &mut NodePointer) {
-    let mut new_ptr = self.remove(n).map(|elem| {
-      self.push_front(elem)
-    }).unwrap();
-    mem::swap(n, &mut new_ptr);
+  fn move_front(&mut self, n: &mut NodePointer<I>) {
+    if self.node(n).is_none() || n.index == self.head {
+      return;
+    }
+    self.unlink(n.index);
+    self.link_between(n.index, I::NONE, self.head);
+    debug_validate(self.size, || self.check_invariants());
   }
 
-  fn next_node(&self, ptr: &NodePointer) -> Option<NodePointer> {
-    match *ptr {
-      NodePointer::Body(i) => {
-        let next = self.spine[i].as_ref()?.next;
+  fn next_node(&self, ptr: &NodePointer<I>) -> Option<NodePointer<I>> {
+    let next = self.node(ptr)?.next;
+    self.pointer_at(next)
+  }
 
-        match next {
-          NodePointer::Body(_) => Some(next),
-          _ => None
-        }
-      },
-      _ => None
+  fn prev_node(&self, ptr: &NodePointer<I>) -> Option<NodePointer<I>> {
+    let prev = self.node(ptr)?.prev;
+    self.pointer_at(prev)
+  }
+
+  fn iter_mut(&mut self) -> VecIterMut<'_, T, I> {
+    VecIterMut {
+      front: self.head,
+      back: self.tail,
+      remaining: self.size,
+      spine: self.spine.as_mut_ptr(),
+      _list: PhantomData,
     }
   }
 
-  fn prev_node(&self, ptr: &NodePointer) -> Option<NodePointer> {
-    match *ptr {
-      NodePointer::Body(i) => {
-        let prev = self.spine[i].as_ref()?.prev;
+  // Swaps every node's links around, walking from the head. O(n).
+  fn reverse(&mut self) {
+    let mut curr = self.head;
+    while curr != I::NONE {
+      let node = self.node_at(curr);
+      mem::swap(&mut node.next, &mut node.prev);
+      curr = node.prev;
+    }
+    mem::swap(&mut self.head, &mut self.tail);
+    debug_validate(self.size, || self.check_invariants());
+  }
 
-        match prev {
-          NodePointer::Body(_) => Some(prev),
-          _ => None
+  // A bottom up merge sort over the next links: merge runs of 1, then
+  // of 2, and so on until one pass merges everything. The prev links
+  // are put back in one walk at the end. O(n log n) time and O(1)
+  // extra space, and the elements stay in their slots, so pointers
+  // keep working.
+  fn sort_by<F>(&mut self, mut cmp: F)
+  where F: FnMut(&T, &T) -> Ordering
+  {
+    if self.size < 2 {
+      return;
+    }
+
+    let mut width = 1;
+    loop {
+      let mut p = mem::replace(&mut self.head, I::NONE);
+      let mut tail = I::NONE;
+      let mut merges = 0;
+
+      while p != I::NONE {
+        merges += 1;
+        // The run at p is width nodes long, or shorter at the end, and
+        // the run at q follows it.
+        let mut q = p;
+        let mut p_left = 0;
+        while p_left < width && q != I::NONE {
+          p_left += 1;
+          q = self.next_at(q);
         }
-      },
-      _ => None
+        let mut q_left = width;
+
+        loop {
+          let q_has = q_left > 0 && q != I::NONE;
+          // Ties go to p, the earlier run, which keeps it stable.
+          let take_p = match (p_left > 0, q_has) {
+            (false, false) => break,
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => cmp(self.elem_at(p), self.elem_at(q)) != Ordering::Greater,
+          };
+          let next = if take_p {
+            let at = p;
+            p = self.next_at(p);
+            p_left -= 1;
+            at
+          } else {
+            let at = q;
+            q = self.next_at(q);
+            q_left -= 1;
+            at
+          };
+          self.set_next(tail, next);
+          tail = next;
+        }
+        p = q;
+      }
+
+      self.node_at(tail).next = I::NONE;
+      if merges <= 1 {
+        break;
+      }
+      width *= 2;
     }
+
+    let mut prev = I::NONE;
+    let mut curr = self.head;
+    while curr != I::NONE {
+      let node = self.node_at(curr);
+      node.prev = prev;
+      prev = curr;
+      curr = node.next;
+    }
+    self.tail = prev;
+    debug_validate(self.size, || self.check_invariants());
   }
 
-  fn head(&self) -> Option<NodePointer> {
-    let next = self.head.next;
-    match next {
-      NodePointer::Body(_) => Some(next),
-      _ => None
+  // After the walk, checks the free list covers exactly the empty slots
+  // that haven't been retired.
+  fn check_invariants(&self) -> Result<(), InvariantError> {
+    let mut reachable = 0;
+    let mut prev = I::NONE;
+    let mut curr = self.head;
+    while curr != I::NONE {
+      let node = self.spine.get(curr.to_usize())
+        .and_then(|slot| slot.node.as_ref())
+        .ok_or(InvariantError::DanglingLink { position: reachable })?;
+      if node.prev != prev {
+        return Err(InvariantError::BrokenBackLink { position: reachable });
+      }
+      prev = curr;
+      curr = node.next;
+      reachable += 1;
     }
+
+    if self.tail != prev {
+      return Err(InvariantError::WrongTail);
+    }
+    if reachable != self.size {
+      return Err(InvariantError::SizeMismatch { size: self.size, reachable });
+    }
+
+    let mut freed = alloc::vec![false; self.spine.len()];
+    for &i in &self.free_list {
+      let index = i.to_usize();
+      match self.spine.get(index) {
+        Some(slot) if slot.node.is_none() && !Self::retired(slot) && !freed[index] => freed[index] = true,
+        _ => return Err(InvariantError::BadFreeSlot { index }),
+      }
+    }
+
+    let retired = self.spine.iter().filter(|slot| Self::retired(slot)).count();
+    let empty = self.spine.iter().filter(|slot| slot.node.is_none()).count() - retired;
+    if empty != self.free_list.len() {
+      return Err(InvariantError::FreeListMismatch { free: self.free_list.len(), empty });
+    }
+    let in_use = self.spine.len() - empty - retired;
+    if in_use != reachable {
+      return Err(InvariantError::Unreachable { count: in_use - reachable });
+    }
+
+    Ok(())
   }
 
-  fn tail(&self) -> Option<NodePointer> {
-    let next = self.tail.prev;
-    match next {
-      NodePointer::Body(_) => Some(next),
-      _ => None
+  fn head(&self) -> Option<NodePointer<I>> {
+    self.pointer_at(self.head)
+  }
+
+  fn tail(&self) -> Option<NodePointer<I>> {
+    self.pointer_at(self.tail)
+  }
+}
+
+// Walks the links through a raw pointer to the spine, so handing out
+// several &mut at once costs nothing up front.
+pub struct VecIterMut<'a, T, I: SpineIndex> {
+  front: I,
+  back: I,
+  remaining: usize,
+  spine: *mut Slot<T, I>,
+  _list: PhantomData<&'a mut [Slot<T, I>]>,
+}
+
+impl<'a, T, I: SpineIndex> VecIterMut<'a, T, I> {
+  // Hands out the element in slot i, along with its next and prev.
+  fn take(&mut self, i: I) -> (&'a mut T, I, I) {
+    self.remaining -= 1;
+
+    // SAFETY: spine came from the &mut borrowed for 'a, which also
+    // keeps the Vec from reallocating, and i is a linked slot, so it is
+    // in bounds and holds a node. remaining stops front and back from
+    // reaching the same slot twice, so the &muts handed out never
+    // overlap.
+    let node = unsafe { (*self.spine.add(i.to_usize())).node.as_mut() };
+    match node {
+      Some(node) => (&mut node.elem, node.next, node.prev),
+      None => panic!("a link leads to an empty slot"),
     }
   }
 }
 
-impl<T: fmt::Display> fmt::Display for VectorLinkedList<T> {
+impl<'a, T, I: SpineIndex> Iterator for VecIterMut<'a, T, I> {
+  type Item = &'a mut T;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 || self.front == I::NONE {
+      return None;
+    }
+
+    let (elem, next, _) = self.take(self.front);
+    self.front = next;
+    Some(elem)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<T, I: SpineIndex> DoubleEndedIterator for VecIterMut<'_, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 || self.back == I::NONE {
+      return None;
+    }
+
+    let (elem, _, prev) = self.take(self.back);
+    self.back = prev;
+    Some(elem)
+  }
+}
+
+impl<T, I: SpineIndex> ExactSizeIterator for VecIterMut<'_, T, I> {}
+impl<T, I: SpineIndex> FusedIterator for VecIterMut<'_, T, I> {}
+
+impl<T: fmt::Display, I: SpineIndex> fmt::Display for IndexedLinkedList<T, I> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     let mut vec: Vec<String> = Vec::new();
     let mut node = self.head();
@@ -254,26 +818,25 @@ impl<T: fmt::Display> fmt::Display for VectorLinkedList<T> {
     while let Some(ptr) = node {
       node = self.next_node(&ptr);
 
-      if let NodePointer::Body(index) = ptr {
-        let elem = self.get(&ptr).unwrap();
-        let prev = match self.prev_node(&ptr) {
-          Some(NodePointer::Body(p_i)) => format!("<- {p_i}"),
-          _ => String::from("HEAD")
-        };
-        let next = match node {
-          Some(NodePointer::Body(n_i)) => format!("{n_i} ->"),
-          _ => String::from("TAIL")
-        };
-
-        vec.push(format!("[{prev} ({index} - {elem}) {next}]"));
-      }
+      let index = ptr.index.to_usize();
+      let elem = self.get(&ptr).unwrap();
+      let prev = match self.prev_node(&ptr) {
+        Some(p) => format!("<- {}", p.index.to_usize()),
+        None => String::from("HEAD")
+      };
+      let next = match node {
+        Some(n) => format!("{} ->", n.index.to_usize()),
+        None => String::from("TAIL")
+      };
+
+      vec.push(format!("[{prev} ({index} - {elem}) {next}]"));
     }
   
     write!(f, "{}", vec.join(""))
   }
 }
 
-impl<T> IntoIterator for VectorLinkedList<T> {
+impl<T, I: SpineIndex> IntoIterator for IndexedLinkedList<T, I> {
   type Item = T;
   type IntoIter = super::DLLIntoIter<T, Self>;
   fn into_iter(self) -> Self::IntoIter {
@@ -284,4 +847,420 @@ impl<T> IntoIterator for VectorLinkedList<T> {
   }
 }
 
+impl<T: fmt::Debug, I: SpineIndex> fmt::Debug for IndexedLinkedList<T, I> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+// Two lists are equal when they hold the same elements in the same
+// order, wherever in the spine those happen to live.
+impl<T: PartialEq, I: SpineIndex> PartialEq for IndexedLinkedList<T, I> {
+  fn eq(&self, other: &Self) -> bool {
+    self.size == other.size && self.iter().eq(other.iter())
+  }
+}
+
+impl<T: Eq, I: SpineIndex> Eq for IndexedLinkedList<T, I> {}
+
+impl<T: Hash, I: SpineIndex> Hash for IndexedLinkedList<T, I> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write_usize(self.size);
+    for elem in self.iter() {
+      elem.hash(state);
+    }
+  }
+}
+
+impl<T, I: SpineIndex> Default for IndexedLinkedList<T, I> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T, I: SpineIndex> Extend<T> for IndexedLinkedList<T, I> {
+  fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+    let iter = iter.into_iter();
+    self.reserve(iter.size_hint().0);
+    for elem in iter {
+      self.push_back(elem);
+    }
+  }
+}
+
+impl<T, I: SpineIndex> FromIterator<T> for IndexedLinkedList<T, I> {
+  fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+    let mut list = Self::new();
+    list.extend(iter);
+    list
+  }
+}
+
 crate::linked_list::macros::dll_tests!(VectorLinkedList);
+
+#[cfg(test)]
+crate::dll_conformance!(VectorLinkedList<u32>);
+
+#[cfg(test)]
+mod compact {
+  use super::*;
+  crate::linked_list::macros::dll_tests!(CompactLinkedList);
+  crate::dll_conformance!(CompactLinkedList<u32>);
+}
+
+#[cfg(test)]
+mod small {
+  use super::*;
+  crate::linked_list::macros::dll_tests!(SmallLinkedList);
+  crate::dll_conformance!(SmallLinkedList<u32>);
+}
+
+
+#[cfg(test)]
+mod generation_test {
+  use super::*;
+
+  #[test]
+  fn stale_pointers() {
+    let mut l = VectorLinkedList::new();
+    let stale = l.push_back(1);
+    l.push_back(2);
+    assert_eq!(l.pop_front(), Some(1));
+
+    // 3 lands in the slot 1 used to live in.
+    let fresh = l.push_back(3);
+    assert_eq!(stale.index, fresh.index);
+    assert_ne!(stale, fresh);
+
+    assert_eq!(l.get(&stale), None);
+    assert_eq!(l.get_mut(&stale), None);
+    assert_eq!(l.next_node(&stale), None);
+    assert_eq!(l.prev_node(&stale), None);
+
+    l.replace_val(&stale, 100);
+    let mut moved = stale;
+    l.move_back(&mut moved);
+    l.move_front(&mut moved);
+    assert_eq!(moved, stale);
+
+    assert_eq!(l.get(&fresh), Some(&3));
+    assert_eq!(l.size(), 2);
+    assert_eq!(l.iter().collect::<Vec<_>>(), vec![&2, &3]);
+
+    // Out of range doesn't panic either.
+    assert_eq!(l.get(&NodePointer { index: 100, generation: 0, list: l.id }), None);
+  }
+
+  #[test]
+  fn moves_keep_pointers() {
+    let mut l = VectorLinkedList::new();
+    let first = l.push_back(1);
+    l.push_back(2);
+
+    // An LRU hit is a move_back, so hot nodes mustn't use up their
+    // slot's generations.
+    let mut moved = first;
+    for _ in 0..10 {
+      l.move_back(&mut moved);
+      l.move_front(&mut moved);
+    }
+    assert_eq!(moved, first);
+    assert_eq!(l.spine[first.index].generation, 0);
+    assert_eq!(l.get(&first), Some(&1));
+    l.move_back(&mut moved);
+    assert_eq!(l.iter().collect::<Vec<_>>(), vec![&2, &1]);
+    assert_eq!(l.tail(), Some(first));
+  }
+
+  #[test]
+  fn worn_out_slots_retire() {
+    let mut l = VectorLinkedList::new();
+    l.push_back(0);
+    l.spine[0].generation = u32::MAX - 2;
+    let last_but_one = l.head().unwrap();
+    l.remove(&last_but_one);
+
+    // The slot has one generation left, so it is reused once more.
+    let last = l.push_back(1);
+    assert_eq!(last, NodePointer { index: 0, generation: u32::MAX - 1, list: l.id });
+    l.remove(&last);
+
+    // Then it is retired rather than wrapping back to 0.
+    assert!(l.free_list.is_empty());
+    let fresh = l.push_back(2);
+    assert_eq!(fresh.index, 1);
+    assert_eq!(l.get(&last), None);
+    assert_eq!(l.get(&NodePointer { index: 0, generation: 0, list: l.id }), None);
+    assert_eq!(l.check_invariants(), Ok(()));
+
+    // Compacting drops it.
+    l.compact();
+    assert_eq!(l.spine.len(), 1);
+  }
+
+  #[test]
+  fn reorder_keeps_pointers() {
+    let mut l = VectorLinkedList::new();
+    let ptrs: Vec<_> = [3, 1, 2].into_iter().map(|i| l.push_back(i)).collect();
+    l.pop_front();
+    let zero = l.push_back(0);
+
+    // Sorting and reversing only move links around, so every pointer
+    // still leads to the same value and the freed slot stays stale.
+    l.sort_by(|a, b| a.cmp(b));
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+    l.reverse();
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![2, 1, 0]);
+    assert_eq!(l.get(&ptrs[0]), None);
+    assert_eq!(l.get(&ptrs[1]), Some(&1));
+    assert_eq!(l.next_node(&ptrs[2]), Some(ptrs[1]));
+    assert_eq!(l.tail(), Some(zero));
+    assert_eq!(l.check_invariants(), Ok(()));
+  }
+}
+
+
+#[cfg(test)]
+mod index_test {
+  use super::*;
+
+  #[test]
+  fn index_sizes() {
+    use std::mem::size_of;
+
+    // Links are just indices now, so they shrink with the index type.
+    assert!(size_of::<Slot<u32, u32>>() < size_of::<Slot<u32, usize>>());
+    assert!(size_of::<Slot<u32, u16>>() < size_of::<Slot<u32, u32>>());
+  }
+
+  #[test]
+  #[should_panic(expected = "can't hold more than")]
+  fn small_list_overflows() {
+    // Indices stop at u16::MAX - 1, since u16::MAX is NONE.
+    let mut l = SmallLinkedList::new();
+    for i in 0..=u16::MAX as usize {
+      l.push_back(i);
+    }
+  }
+
+  #[test]
+  fn small_list_reuses_slots_when_full() {
+    let mut l = SmallLinkedList::new();
+    let first = l.push_back(0);
+    for i in 1..u16::MAX as usize {
+      l.push_back(i);
+    }
+
+    assert_eq!(l.size(), u16::MAX as usize);
+    assert!(!l.has_room(1));
+    assert_eq!(l.try_push_front(0), Err(crate::error::Error::CapacityExceeded));
+    l.remove(&first);
+    assert!(l.has_room(1));
+    l.push_back(0);
+    assert_eq!(l.peek_back(), Some(&0));
+
+    let mut other = SmallLinkedList::new();
+    other.push_back(1);
+    assert_eq!(l.try_append(&mut other), Err(crate::error::Error::CapacityExceeded));
+    assert_eq!(other.size(), 1);
+  }
+}
+
+
+#[cfg(test)]
+mod compact_test {
+  use super::*;
+
+  #[test]
+  fn compact() {
+    let mut l = VectorLinkedList::with_capacity(8);
+    assert!(l.capacity() >= 8);
+
+    let ptrs: Vec<_> = (0..8).map(|i| l.push_back(i)).collect();
+    for ptr in ptrs.iter().step_by(2) {
+      l.remove(ptr);
+    }
+    let mut three = ptrs[3];
+    l.move_front(&mut three);
+    assert_eq!(l.spine.len(), 8);
+
+    let remap = l.shrink_to_fit();
+    assert_eq!(l.spine.len(), 4);
+    assert!(l.free_list.is_empty());
+    assert_eq!(remap.len(), 4);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![3, 1, 5, 7]);
+
+    // Old pointers are stale, even the ones whose slot is still in use,
+    // and the remapped ones lead to the same values.
+    for (i, ptr) in ptrs.iter().enumerate() {
+      assert_eq!(l.get(ptr), None);
+      if let Some(new_ptr) = remap.get(ptr) {
+        assert_eq!(l.get(new_ptr), Some(&(i as i32)));
+      }
+    }
+    assert_eq!(l.get(&remap[&three]), Some(&3));
+    let tail = l.tail().unwrap();
+    assert_eq!(l.prev_node(&tail).and_then(|p| l.get(&p).copied()), Some(5));
+
+    // Slots dropped by compacting don't revive old pointers once they
+    // are pushed again.
+    for i in 8..12 {
+      l.push_back(i);
+    }
+    for ptr in &ptrs {
+      assert_eq!(l.get(ptr), None);
+    }
+    assert_eq!(l.size(), 8);
+
+    // Compacting an empty list leaves it usable.
+    while l.pop_front().is_some() {}
+    assert!(l.compact().is_empty());
+    assert_eq!(l.peek_front(), None);
+    l.push_front(1);
+    l.push_back(2);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+
+    l.reserve(10);
+    assert!(l.capacity() >= 10);
+  }
+}
+
+
+#[cfg(test)]
+mod remap_test {
+  use super::*;
+
+  #[test]
+  fn moves_hand_back_remaps() {
+    let mut l = VectorLinkedList::new();
+    let mut other = VectorLinkedList::new();
+    let mine: Vec<_> = (0..3).map(|i| l.push_back(i)).collect();
+    let theirs: Vec<_> = (10..13).map(|i| other.push_back(i)).collect();
+    l.remove(&mine[1]);
+
+    // The free slot is used first, then the spine grows once.
+    let remap = l.append_with_remap(&mut other);
+    assert_eq!(remap.len(), 3);
+    assert_eq!(remap[&theirs[0]].index, mine[1].index);
+    assert_eq!(l.spine.len(), 5);
+    for (i, ptr) in theirs.iter().enumerate() {
+      assert_eq!(l.get(&remap[ptr]), Some(&(10 + i as i32)));
+    }
+    assert_eq!(l.get(&mine[2]), Some(&2));
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![0, 2, 10, 11, 12]);
+
+    // other is empty, and its old slots don't come back to life.
+    assert_eq!(other.size(), 0);
+    other.push_back(20);
+    assert_eq!(other.get(&theirs[0]), None);
+
+    let (mut rest, remap) = l.split_off_with_remap(&mine[2]);
+    assert_eq!(rest.iter().copied().collect::<Vec<_>>(), vec![2, 10, 11, 12]);
+    assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![0]);
+    assert_eq!(l.get(&mine[2]), None);
+    assert_eq!(rest.get(&remap[&mine[2]]), Some(&2));
+    assert_eq!(rest.spine.len(), 4);
+
+    let remap = rest.splice_after_with_remap(&remap[&mine[2]], &mut l);
+    assert_eq!(rest.iter().copied().collect::<Vec<_>>(), vec![2, 0, 10, 11, 12]);
+    assert_eq!(rest.get(&remap[&mine[0]]), Some(&0));
+    assert_eq!(rest.check_invariants(), Ok(()));
+
+    // A stale pointer leaves both lists alone.
+    let stale = NodePointer { index: 100, generation: 0, list: rest.id };
+    assert!(rest.splice_after_with_remap(&stale, &mut other).is_empty());
+    assert_eq!(other.size(), 1);
+    assert_eq!(rest.split_off_with_remap(&stale).0.size(), 0);
+    assert_eq!(rest.size(), 5);
+  }
+}
+
+
+#[cfg(test)]
+mod invariant_test {
+  use super::*;
+
+  fn list() -> VectorLinkedList<i32> {
+    let mut l = VectorLinkedList::new();
+    for i in 0..4 {
+      l.push_back(i);
+    }
+    l.pop_front();
+    l
+  }
+
+  #[test]
+  fn healthy() {
+    let mut l = list();
+    assert_eq!(l.check_invariants(), Ok(()));
+    l.compact();
+    assert_eq!(l.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn broken_links() {
+    let mut l = list();
+    l.node_at(2).prev = 3;
+    assert_eq!(l.check_invariants(), Err(InvariantError::BrokenBackLink { position: 1 }));
+
+    let mut l = list();
+    l.node_at(2).next = 0;
+    assert_eq!(l.check_invariants(), Err(InvariantError::DanglingLink { position: 2 }));
+
+    // A circle back to the head is caught by its back link.
+    let mut l = list();
+    l.node_at(3).next = 1;
+    assert_eq!(l.check_invariants(), Err(InvariantError::BrokenBackLink { position: 3 }));
+
+    let mut l = list();
+    l.tail = 2;
+    assert_eq!(l.check_invariants(), Err(InvariantError::WrongTail));
+  }
+
+  #[test]
+  fn bad_counts() {
+    let mut l = list();
+    l.size = 4;
+    assert_eq!(l.check_invariants(), Err(InvariantError::SizeMismatch { size: 4, reachable: 3 }));
+
+    let mut l = list();
+    l.free_list.push(1);
+    assert_eq!(l.check_invariants(), Err(InvariantError::BadFreeSlot { index: 1 }));
+
+    let mut l = list();
+    l.free_list.push(0);
+    assert_eq!(l.check_invariants(), Err(InvariantError::BadFreeSlot { index: 0 }));
+
+    let mut l = list();
+    l.free_list.clear();
+    assert_eq!(l.check_invariants(), Err(InvariantError::FreeListMismatch { free: 0, empty: 1 }));
+
+    // Cutting the last node off leaves it in a slot nothing can reach.
+    let mut l = list();
+    l.node_at(2).next = usize::NONE;
+    l.tail = 2;
+    l.size = 2;
+    assert_eq!(l.check_invariants(), Err(InvariantError::Unreachable { count: 1 }));
+  }
+}
+
+#[cfg(test)]
+mod traits_test {
+  use super::*;
+
+  #[test]
+  fn clone_has_its_own_id() {
+    let mut l: VectorLinkedList<i32> = (1..6).collect();
+
+    // The clone keeps the layout, but it is another list, so old
+    // pointers don't reach it.
+    let head = l.head().unwrap();
+    let mut copy = l.clone();
+    assert_eq!(l, copy);
+    assert_eq!(copy.get(&head), None);
+    assert_eq!(copy.remove(&head), None);
+    *l.get_mut(&head).unwrap() = 10;
+    assert_eq!(copy.peek_front(), Some(&1));
+    assert_ne!(l, copy);
+  }
+}
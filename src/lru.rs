@@ -1,81 +1,363 @@
-use std::collections::HashMap;
-use std::hash::Hash;
-use crate::linked_list::DLL;
-use std::marker::PhantomData;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use crate::error::Error;
+use crate::linked_list::{DLL, DLLIterator, InvariantError, debug_validate};
+use crate::map::{DefaultHashBuilder, HashMap};
+use core::marker::PhantomData;
+use core::ops::Deref;
 
 pub mod veclru;
 pub mod cellru;
+#[cfg(target_has_atomic = "64")]
+pub mod ptrlru;
+pub mod arraylru;
+pub mod backedlru;
+// These share a cache between threads, so they need std.
+#[cfg(feature = "std")]
+pub mod synclru;
+#[cfg(feature = "std")]
+pub mod shardedlru;
+#[cfg(feature = "std")]
+pub mod bufferedlru;
+#[cfg(feature = "std")]
+pub mod loadinglru;
+#[cfg(feature = "std")]
+pub mod expiringlru;
 
 
-pub struct KeyHolder<K: Eq + Hash + Copy, T, L: DLL<(K, T)>> {
+// S hashes the keys. The default is DoS resistant, see crate::map; for
+// small trusted keys like integers, FxBuildHasher is a lot faster.
+pub struct KeyHolder<K: Eq + Hash + Copy, T, L: DLL<(K, T)>, S = DefaultHashBuilder> {
   list: L,
-  hash: HashMap<K, L::Pointer>,
+  hash: HashMap<K, L::Pointer, S>,
   size: usize,
   capacity: usize,
   _marker: PhantomData<T>,
 }
 
+// What get and peek hand back: the list's own reference to the whole
+// (key, value) entry, showing only the value. For the Vec list this is
+// a plain reference, for the Cell list it is a guard on the node.
+pub struct ValueRef<K, T, G> {
+  entry: G,
+  _marker: PhantomData<(K, T)>,
+}
+
+// The ValueRef that get and peek return for a given list type.
+pub type ListValueRef<'a, K, T, L> = ValueRef<K, T, <L as DLL<(K, T)>>::Ref<'a>>;
+
+impl<K, T, G: Deref<Target = (K, T)>> ValueRef<K, T, G> {
+  fn new(entry: G) -> Self {
+    ValueRef { entry, _marker: PhantomData }
+  }
+
+  // The underlying list reference, for when a plain &(K, T) is more
+  // useful than the guard.
+  pub fn into_inner(self) -> G {
+    self.entry
+  }
+}
+
+impl<K, T, G: Deref<Target = (K, T)>> Deref for ValueRef<K, T, G> {
+  type Target = T;
+  fn deref(&self) -> &T {
+    &self.entry.1
+  }
+}
+
+impl<K: Eq + Hash + Copy, T, L: DLL<(K, T)>, S: BuildHasher> KeyHolder<K, T, L, S> {
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  // Looks a key up without marking it as used.
+  pub fn peek(&self, key: &K) -> Option<ListValueRef<'_, K, T, L>> {
+    let ptr = self.hash.get(key)?;
+    self.list.get(ptr).map(ValueRef::new)
+  }
+
+  // Walks the entries from least to most recently used, without
+  // touching their recency.
+  pub fn iter(&self) -> DLLIterator<'_, (K, T), L> {
+    self.list.iter()
+  }
+
+  // The work behind LRU::try_put. It lives here rather than in the
+  // trait so that Lru can put without going through LRU, which needs
+  // a Default hasher.
+  pub fn try_put(&mut self, key: K, val: T) -> Result<(), Error> {
+    match self.hash.get_mut(&key) {
+      // Entry exists! Replace it, THEN move it back
+      Some(ptr) => {
+        self.list.replace_val(ptr, (key, val));
+        self.list.move_back(ptr);
+      },
+
+      // A cache with no room keeps nothing.
+      None if self.capacity == 0 => {},
+
+      // New entry! Make room if we're full, then push value to back
+      // of the list
+      None => {
+        // The capacity isn't 0, so a full cache has a front to evict,
+        // and evicting it always leaves room for the new entry.
+        if self.size == self.capacity {
+          if let Some((key, _)) = self.list.pop_front() {
+            self.size -= 1;
+            self.hash.remove(&key);
+          }
+        }
+
+        let new_ptr = self.list.try_push_back((key, val))?;
+        self.size += 1;
+        self.hash.insert(key, new_ptr);
+      }
+    };
+
+    debug_validate(self.size, || self.check_invariants());
+    Ok(())
+  }
+
+  // Checks the list, then that every key in the map leads to its own
+  // entry and there are as many keys as entries, so the two match one
+  // to one.
+  pub fn check_invariants(&self) -> Result<(), InvariantError> {
+    self.list.check_invariants()?;
+
+    let entries = self.list.size();
+    if self.size != entries {
+      return Err(InvariantError::SizeMismatch { size: self.size, reachable: entries });
+    }
+    if self.size > self.capacity {
+      return Err(InvariantError::OverCapacity { size: self.size, capacity: self.capacity });
+    }
+    if self.hash.len() != entries {
+      return Err(InvariantError::KeyCountMismatch { keys: self.hash.len(), entries });
+    }
+
+    for (key, ptr) in self.hash.iter() {
+      match self.list.get(ptr) {
+        None => return Err(InvariantError::StaleKey),
+        Some(entry) if entry.0 != *key => return Err(InvariantError::WrongKey),
+        Some(_) => {},
+      }
+    }
+
+    Ok(())
+  }
+}
+
+// The LRU over any list. VecLRU, CellLRU and the rest are this with
+// their list filled in; to use your own DLL, name it as L here.
+pub struct Lru<K: Eq + Hash + Copy, T, L: DLL<(K, T)>, S = DefaultHashBuilder> {
+  key_holder: KeyHolder<K, T, L, S>,
+}
+
+impl<K: Eq + Hash + Copy, T, L: DLL<(K, T)>> Lru<K, T, L> {
+  // Only for the default hasher, like HashMap::new, so that
+  // VecLRU::new(n) doesn't need a type annotation.
+  pub fn new(capacity: usize) -> Self {
+    Self::with_hasher(capacity, DefaultHashBuilder::default())
+  }
+
+  pub fn try_new(capacity: usize) -> Result<Self, Error> {
+    Self::try_with_hasher(capacity, DefaultHashBuilder::default())
+  }
+}
+
+impl<K: Eq + Hash + Copy, T, L: DLL<(K, T)>, S: BuildHasher> Lru<K, T, L, S> {
+  // Panics if the list has a max_size smaller than capacity.
+  pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+    match Self::try_with_hasher(capacity, hash_builder) {
+      Ok(lru) => lru,
+      Err(_) => panic!(
+        "An LRU over {} nodes can't have a capacity of {capacity}",
+        L::max_size().unwrap_or_default(),
+      ),
+    }
+  }
+
+  // Error::CapacityExceeded if the list has a max_size smaller than
+  // capacity.
+  pub fn try_with_hasher(capacity: usize, hash_builder: S) -> Result<Self, Error> {
+    let hash = match L::max_size() {
+      Some(max) if capacity > max => return Err(Error::CapacityExceeded),
+      // A fixed size list is there to stay off the heap, so the map is
+      // sized up front too. Keeping the table at most half full lets
+      // removed entries be cleaned up in place, so it never has to grow.
+      Some(max) => HashMap::with_capacity_and_hasher(2 * max, hash_builder),
+      None => HashMap::with_hasher(hash_builder),
+    };
+
+    Ok(Lru {
+      key_holder: KeyHolder {
+        hash,
+        list: L::new(),
+        _marker: PhantomData,
+        size: 0,
+        capacity,
+      },
+    })
+  }
+
+  // Same, but makes room for capacity entries up front instead of
+  // growing as the cache fills.
+  pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+    let mut lru = Self::with_hasher(capacity, hash_builder);
+    lru.key_holder.hash.reserve(capacity);
+    lru.key_holder.list.reserve(capacity);
+    lru
+  }
+
+  pub fn peek(&self, key: &K) -> Option<ListValueRef<'_, K, T, L>> {
+    self.key_holder.peek(key)
+  }
+}
+
 pub trait LRU<K, T>
 where K: Eq + Hash + Copy {
   type List: DLL<(K, T)>;
+  type Hasher: BuildHasher;
 
+  // Builds the hasher with Default. The caches also have a with_hasher
+  // for handing one in.
   fn new(capacity: usize) -> Self;
 
-  fn key_holder(&mut self) -> &mut KeyHolder<K, T, Self::List>;
+  fn key_holder(&mut self) -> &mut KeyHolder<K, T, Self::List, Self::Hasher>;
 
-  fn get<'a>(&'a mut self, key: &'a K) -> Option<&'_ T> {
+  fn get<'a>(&'a mut self, key: &K) -> Option<ListValueRef<'a, K, T, Self::List>>
+  where Self::List: 'a {
     let holder = self.key_holder();
-    let mut ptr = match holder.hash.get_mut(&key) {
-      Some(p) => p,
-      None => { return None }
-    };
+    let ptr = holder.hash.get_mut(key)?;
 
-    holder.list.move_back(&mut ptr);
+    holder.list.move_back(ptr);
+    // Only the list changed, so that is all there is to check.
+    debug_validate(holder.size, || holder.list.check_invariants());
+    holder.list.get(ptr).map(ValueRef::new)
+  }
 
-    if let Some(tup) = holder.list.get(ptr) {
-      return Some(&tup.1)
+  fn remove(&mut self, key: &K) -> Option<T> {
+    let holder = self.key_holder();
+    let ptr = holder.hash.remove(key)?;
+    holder.size -= 1;
+    let val = holder.list.remove(&ptr).map(|(_, val)| val);
+    debug_validate(holder.size, || holder.check_invariants());
+    val
+  }
+
+  // Panics if the list runs out of room before the cache reaches its
+  // capacity, which only a list with a ceiling like SmallLinkedList
+  // can. try_put gives an error instead.
+  fn put(&mut self, key: K, val: T) {
+    if let Err(err) = self.try_put(key, val) {
+      panic!("LRU::put: {err}");
     }
+  }
 
-    None
+  // Error::CapacityExceeded, with nothing evicted and val dropped, if
+  // the list has no room for a new entry.
+  fn try_put(&mut self, key: K, val: T) -> Result<(), Error> {
+    self.key_holder().try_put(key, val)
   }
+}
 
-  fn put(&mut self, key: K, val: T) {
-    let holder = self.key_holder();
-    if holder.size == holder.capacity {
-      match holder.list.pop_front() {
-        Some((key, _)) => {
-          holder.size -= 1;
-          holder.hash.remove(&key)
-        },
-        None => panic!("SIZE MAKES NO SENSE") 
-      };
+impl<K, T, L, S> LRU<K, T> for Lru<K, T, L, S>
+where K: Eq + Hash + Copy, L: DLL<(K, T)>, S: BuildHasher + Default {
+  type List = L;
+  type Hasher = S;
+
+  fn new(capacity: usize) -> Self {
+    Self::with_hasher(capacity, S::default())
+  }
+
+  fn key_holder(&mut self) -> &mut KeyHolder<K, T, L, S> {
+    &mut self.key_holder
+  }
+}
+
+// Entries from least to most recently used.
+impl<K, T, L, S> fmt::Debug for Lru<K, T, L, S>
+where K: Eq + Hash + Copy + fmt::Debug, T: fmt::Debug, L: DLL<(K, T)> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut map = f.debug_map();
+    for entry in self.key_holder.list.iter() {
+      map.entry(&entry.0, &entry.1);
     }
+    map.finish()
+  }
+}
 
-    let existing = holder.hash.get_mut(&key);
-
-    // I have to keep using the "linked_list" and "hash_table"
-    // getters, because self can only have one mutable
-    // reference at a time, and both of those data structures are
-    // mutable. This seems clunkier than assigning to 
-    // a variable, but I don't know if there is any way around it?
-    // Re-using the "list" variable above after calling "self.hash_table"
-    // does not work.
-    // If I had access to the underlying struct then that would work
-    // too but... I want to keep this logic in the trait.
-    match existing {
-      // Entry exists! Replace it, THEN move it back
-      Some(mut ptr) => {
-        holder.list.replace_val(&ptr, (key, val));
-        holder.list.move_back(&mut ptr);
-      },
+// Pointers into one list mean nothing to another, so the clone is
+// built up entry by entry, in the same recency order, instead of
+// copying the map.
+impl<K, T, L, S> Clone for Lru<K, T, L, S>
+where K: Eq + Hash + Copy, T: Clone, L: DLL<(K, T)>, S: BuildHasher + Clone {
+  fn clone(&self) -> Self {
+    let holder = &self.key_holder;
+    let mut lru = Self::with_hasher(holder.capacity, holder.hash.hasher().clone());
+    let clone = &mut lru.key_holder;
+    clone.hash.reserve(holder.size);
+    clone.list.reserve(holder.size);
 
-      // New entry! Push value to back of the list
-      None => {
-        let new_ptr = holder.list.push_back((key, val));
-        holder.size += 1;
-        holder.hash.insert(key, new_ptr);
+    for entry in holder.iter() {
+      let key = entry.0;
+      let ptr = clone.list.push_back((key, entry.1.clone()));
+      clone.hash.insert(key, ptr);
+    }
+    clone.size = holder.size;
+    lru
+  }
+}
+
+// Puts each pair in turn, so later ones win and count as more recent.
+// Panics where put would.
+impl<K, T, L, S> Extend<(K, T)> for Lru<K, T, L, S>
+where K: Eq + Hash + Copy, L: DLL<(K, T)>, S: BuildHasher {
+  fn extend<I: IntoIterator<Item = (K, T)>>(&mut self, iter: I) {
+    for (key, val) in iter {
+      if let Err(err) = self.key_holder.try_put(key, val) {
+        panic!("LRU::put: {err}");
       }
-    };
+    }
+  }
+}
+
+// The capacity is the number of distinct keys handed in, so nothing
+// gets evicted on the way, but never less than 1: an empty cache made
+// this way can still take puts. Panics if the list has a max_size
+// smaller than that, like with_hasher.
+impl<K, T, L, S> FromIterator<(K, T)> for Lru<K, T, L, S>
+where K: Eq + Hash + Copy, L: DLL<(K, T)>, S: BuildHasher + Default {
+  fn from_iter<I: IntoIterator<Item = (K, T)>>(iter: I) -> Self {
+    // Starts out as big as the list allows and takes everything in one
+    // pass, then the capacity comes down to what was put.
+    let max = L::max_size();
+    let iter = iter.into_iter();
+    let hint = max.map_or(iter.size_hint().0, |max| max.min(iter.size_hint().0));
+    let mut lru = Self::with_hasher(0, S::default());
+    let holder = &mut lru.key_holder;
+    holder.hash.reserve(hint);
+    holder.list.reserve(hint);
+    holder.capacity = max.unwrap_or(usize::MAX);
+
+    for (key, val) in iter {
+      if holder.size == holder.capacity && !holder.hash.contains_key(&key) {
+        panic!("An LRU over {} nodes can't hold more than {0} keys", holder.capacity);
+      }
+      if let Err(err) = holder.try_put(key, val) {
+        panic!("LRU::put: {err}");
+      }
+    }
+
+    holder.capacity = holder.size.max(1);
+    if max.is_some_and(|max| holder.capacity > max) {
+      panic!("An LRU over {} nodes can't have a capacity of {}", max.unwrap_or_default(), holder.capacity);
+    }
+    lru
   }
 }
 
@@ -86,36 +368,37 @@ mod macros {
       #[cfg(test)]
       mod test {
         use super::*;
+        use crate::lru::LRU;
 
         #[test]
         fn test() {
           let mut lru: $type<&str, i32>;
           lru = $type::new(3);
         
-          assert_eq!(lru.get(&"Hello"), None);
+          assert_eq!(lru.get(&"Hello").as_deref(), None);
         
           lru.put(&"Hello", 1);
           lru.put(&"Amy", 2);
           lru.put(&"Santiago", 3);
         
-          assert_eq!(lru.get(&"Hello").unwrap(), &1);
-          assert_eq!(lru.get(&"Amy").unwrap(), &2);
-          assert_eq!(lru.get(&"Santiago").unwrap(), &3);
+          assert_eq!(lru.get(&"Hello").as_deref(), Some(&1));
+          assert_eq!(lru.get(&"Amy").as_deref(), Some(&2));
+          assert_eq!(lru.get(&"Santiago").as_deref(), Some(&3));
         
           // Removes correct ones from cache
           lru.put(&"Buster 1", 4);
-          assert_eq!(lru.get(&"Hello"), None);
+          assert_eq!(lru.get(&"Hello").as_deref(), None);
           lru.put(&"Buster 2", 5);
           lru.put(&"Buster 3", 6);
-          assert_eq!(lru.get(&"Amy"), None);
-          assert_eq!(lru.get(&"Santiago"), None);
+          assert_eq!(lru.get(&"Amy").as_deref(), None);
+          assert_eq!(lru.get(&"Santiago").as_deref(), None);
         
           // LRU functionality works
-          assert_eq!(lru.get(&"Buster 1").unwrap(), &4);
+          assert_eq!(lru.get(&"Buster 1").as_deref(), Some(&4));
           // Least recently used is now Buster 2, which should have been removed
           lru.put(&"Bla Bla", 10);
-          assert_eq!(lru.get(&"Buster 1").unwrap(), &4);
-          assert_eq!(lru.get(&"Buster 2"), None);
+          assert_eq!(lru.get(&"Buster 1").as_deref(), Some(&4));
+          assert_eq!(lru.get(&"Buster 2").as_deref(), None);
 
 
           let mut other_lru: $type<i32, i32>;
@@ -127,12 +410,83 @@ mod macros {
           other_lru.put(a, 12);
           other_lru.put(11, 100);
           a += 1;
-          assert_eq!(other_lru.get(&a), Some(&100));
-          assert_eq!(other_lru.get(&10), Some(&12));
+          assert_eq!(other_lru.get(&a).as_deref(), Some(&100));
+          assert_eq!(other_lru.get(&10).as_deref(), Some(&12));
+
+          // Updating a key that is already cached doesn't evict anything
+          other_lru.put(12, 200);
+          other_lru.put(11, 101);
+          assert_eq!(other_lru.get(&10).as_deref(), Some(&12));
+          assert_eq!(other_lru.get(&11).as_deref(), Some(&101));
+          assert_eq!(other_lru.get(&12).as_deref(), Some(&200));
+
+          // Removing a key frees up its slot
+          assert_eq!(other_lru.remove(&11), Some(101));
+          assert_eq!(other_lru.remove(&11), None);
+          assert_eq!(other_lru.get(&11).as_deref(), None);
+          other_lru.put(13, 300);
+          assert_eq!(other_lru.get(&10).as_deref(), Some(&12));
+          assert_eq!(other_lru.get(&12).as_deref(), Some(&200));
+          assert_eq!(other_lru.get(&13).as_deref(), Some(&300));
+          assert_eq!(other_lru.key_holder().size(), 3);
+
+          // A cache with no room keeps nothing
+          let mut empty: $type<i32, i32>;
+          empty = $type::new(0);
+          empty.put(1, 1);
+          assert_eq!(empty.get(&1).as_deref(), None);
+          assert_eq!(empty.key_holder().size(), 0);
         }
       }
     }
   }
   
   pub(crate) use lru_tests;
-}
\ No newline at end of file
+}
+
+// Any DLL works as the list, not just the ones with an alias.
+#[cfg(test)]
+type SmallLRU<K, T> = Lru<K, T, crate::linked_list::veclist::SmallLinkedList<(K, T)>>;
+
+#[cfg(test)]
+macros::lru_tests!(SmallLRU);
+
+#[cfg(test)]
+crate::lru_conformance!(SmallLRU<u32, u32>);
+
+
+#[cfg(test)]
+mod capacity_test {
+  use super::*;
+  use crate::linked_list::arraylist::ArrayLinkedList;
+
+  #[test]
+  fn list_runs_out_of_room() {
+    // A u16 spine stops at 65535 nodes, whatever the cache's capacity.
+    let mut lru: SmallLRU<u32, u32> = Lru::new(70_000);
+    for i in 0..u16::MAX as u32 {
+      lru.put(i, i);
+    }
+    assert_eq!(lru.try_put(100_000, 1), Err(Error::CapacityExceeded));
+    assert!(lru.peek(&100_000).is_none());
+    assert_eq!(lru.peek(&0).as_deref(), Some(&0));
+
+    // Updating doesn't need room.
+    assert_eq!(lru.try_put(0, 5), Ok(()));
+    assert_eq!(lru.key_holder().size(), u16::MAX as usize);
+  }
+
+  #[test]
+  fn capacity_past_max_size() {
+    type TinyLRU = Lru<u32, u32, ArrayLinkedList<(u32, u32), 4>>;
+    assert!(matches!(TinyLRU::try_new(5), Err(Error::CapacityExceeded)));
+    let mut lru = TinyLRU::try_new(4).unwrap();
+    for i in 0..10 {
+      assert_eq!(lru.try_put(i, i), Ok(()));
+    }
+    assert_eq!(lru.key_holder().size(), 4);
+
+    let result = std::panic::catch_unwind(|| TinyLRU::new(5));
+    assert!(result.is_err());
+  }
+}
@@ -0,0 +1,40 @@
+use crate::linked_list::arraylist;
+use crate::lru::Lru;
+use crate::map::DefaultHashBuilder;
+
+
+// An LRU whose list lives in a fixed array of N entries, so after new
+// it doesn't allocate: the list can't grow, and the map is sized up
+// front. new panics if capacity is more than N; try_new gives an error.
+pub type ArrayLRU<K, T, const N: usize, S = DefaultHashBuilder> =
+  Lru<K, T, arraylist::ArrayLinkedList<(K, T), N>, S>;
+
+
+#[cfg(test)]
+type TestLRU<K, T> = ArrayLRU<K, T, 8>;
+
+#[cfg(test)]
+crate::lru::macros::lru_tests!(TestLRU);
+
+#[cfg(test)]
+crate::lru_conformance!(TestLRU<u32, u32>);
+
+#[cfg(test)]
+mod from_iter_test {
+  use super::*;
+  use crate::lru::LRU;
+
+  #[test]
+  fn fills_to_n() {
+    // Repeated keys don't count against N.
+    let mut lru: ArrayLRU<i32, i32, 2> = [(1, 1), (2, 2), (1, 10)].into_iter().collect();
+    assert_eq!(lru.key_holder().capacity(), 2);
+    assert_eq!(lru.get(&1).as_deref(), Some(&10));
+  }
+
+  #[test]
+  #[should_panic(expected = "can't hold more than 2 keys")]
+  fn more_keys_than_n() {
+    let _: ArrayLRU<i32, i32, 2> = (0..3).map(|i| (i, i)).collect();
+  }
+}
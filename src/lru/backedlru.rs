@@ -0,0 +1,321 @@
+use core::hash::Hash;
+use crate::lru::LRU;
+use crate::lru::veclru::VecLRU;
+use crate::linked_list::DLL;
+
+
+// The slow store that sits behind a BackedLru.
+pub trait BackingStore<K, T> {
+  type Error;
+
+  fn load(&mut self, key: &K) -> Result<Option<T>, Self::Error>;
+  fn store(&mut self, key: &K, val: &T) -> Result<(), Self::Error>;
+  fn delete(&mut self, key: &K) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WritePolicy {
+  // Every put and delete reaches the store before it returns.
+  WriteThrough,
+  // Puts and deletes only mark the entry dirty. The store sees them when
+  // the entry is evicted or on flush.
+  WriteBehind,
+}
+
+struct Entry<T> {
+  // None is a key we know is not in the store, either because it was
+  // deleted or because a load came back empty.
+  val: Option<T>,
+  dirty: bool,
+}
+
+// An LRU cache in front of a BackingStore. Misses are loaded from the
+// store, and writes go to the store according to the WritePolicy.
+//
+// With WriteBehind, dirty entries are written out before they are
+// evicted, so nothing is lost by falling out of the cache. Dropping the
+// cache flushes whatever is still dirty, but errors can't be reported
+// from there: call flush first if you need to know.
+pub struct BackedLru<K: Eq + Hash + Copy, T, S: BackingStore<K, T>> {
+  lru: VecLRU<K, Entry<T>>,
+  store: S,
+  policy: WritePolicy,
+}
+
+impl<K: Eq + Hash + Copy, T, S: BackingStore<K, T>> BackedLru<K, T, S> {
+  // Panics on a capacity of 0. Dirty entries are only written out when
+  // they leave the cache, so one that never gets in would be lost, and
+  // get hands back a reference into the cache.
+  pub fn new(capacity: usize, store: S, policy: WritePolicy) -> Self {
+    assert!(capacity > 0, "A BackedLru needs room for at least one entry");
+    BackedLru {
+      lru: VecLRU::new(capacity),
+      store,
+      policy,
+    }
+  }
+
+  pub fn get<'a>(&'a mut self, key: &'a K) -> Result<Option<&'a T>, S::Error> {
+    if self.lru.key_holder().hash.contains_key(key) {
+      return Ok(self.lru.get(key).and_then(|entry| entry.into_inner().1.val.as_ref()));
+    }
+
+    let val = self.store.load(key)?;
+    self.insert(*key, Entry { val, dirty: false })?;
+    Ok(self.lru.peek(key).and_then(|entry| entry.into_inner().1.val.as_ref()))
+  }
+
+  pub fn put(&mut self, key: K, val: T) -> Result<(), S::Error> {
+    let dirty = match self.policy {
+      WritePolicy::WriteThrough => {
+        self.store.store(&key, &val)?;
+        false
+      },
+      WritePolicy::WriteBehind => true,
+    };
+
+    self.insert(key, Entry { val: Some(val), dirty })
+  }
+
+  pub fn delete(&mut self, key: &K) -> Result<(), S::Error> {
+    let dirty = match self.policy {
+      WritePolicy::WriteThrough => {
+        self.store.delete(key)?;
+        false
+      },
+      WritePolicy::WriteBehind => true,
+    };
+
+    self.insert(*key, Entry { val: None, dirty })
+  }
+
+  // Writes every dirty entry to the store. Stops at the first error,
+  // leaving that entry and any after it dirty.
+  pub fn flush(&mut self) -> Result<(), S::Error> {
+    let store = &mut self.store;
+    for (key, entry) in self.lru.key_holder().list.iter_mut() {
+      if entry.dirty {
+        Self::write_back(store, key, entry)?;
+        entry.dirty = false;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn store(&self) -> &S {
+    &self.store
+  }
+
+  pub fn store_mut(&mut self) -> &mut S {
+    &mut self.store
+  }
+
+  fn insert(&mut self, key: K, entry: Entry<T>) -> Result<(), S::Error> {
+    let holder = self.lru.key_holder();
+
+    // put is about to evict the front entry. If it hasn't reached the
+    // store yet, write it now, so a failing store leaves the cache as
+    // it was instead of losing the write.
+    if !holder.hash.contains_key(&key) && holder.size == holder.capacity {
+      if let Some(front) = holder.list.head() {
+        if let Some((front_key, front_entry)) = holder.list.get_mut(&front) {
+          if front_entry.dirty {
+            Self::write_back(&mut self.store, front_key, front_entry)?;
+            front_entry.dirty = false;
+          }
+        }
+      }
+    }
+
+    self.lru.put(key, entry);
+    Ok(())
+  }
+
+  fn write_back(store: &mut S, key: &K, entry: &Entry<T>) -> Result<(), S::Error> {
+    match &entry.val {
+      Some(val) => store.store(key, val),
+      None => store.delete(key),
+    }
+  }
+}
+
+impl<K: Eq + Hash + Copy, T, S: BackingStore<K, T>> Drop for BackedLru<K, T, S> {
+  fn drop(&mut self) {
+    let _ = self.flush();
+  }
+}
+
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::cell::RefCell;
+  use std::collections::HashMap;
+  use std::rc::Rc;
+
+  #[derive(Default)]
+  struct MemoryStore {
+    data: HashMap<i32, String>,
+    loads: usize,
+    writes: usize,
+    fail: bool,
+  }
+
+  impl BackingStore<i32, String> for MemoryStore {
+    type Error = String;
+
+    fn load(&mut self, key: &i32) -> Result<Option<String>, String> {
+      self.loads += 1;
+      Ok(self.data.get(key).cloned())
+    }
+
+    fn store(&mut self, key: &i32, val: &String) -> Result<(), String> {
+      if self.fail {
+        return Err(format!("can't store {key}"));
+      }
+      self.writes += 1;
+      self.data.insert(*key, val.clone());
+      Ok(())
+    }
+
+    fn delete(&mut self, key: &i32) -> Result<(), String> {
+      if self.fail {
+        return Err(format!("can't delete {key}"));
+      }
+      self.writes += 1;
+      self.data.remove(key);
+      Ok(())
+    }
+  }
+
+  fn s(val: &str) -> String {
+    String::from(val)
+  }
+
+  #[test]
+  fn read_through() {
+    let mut store = MemoryStore::default();
+    store.data.insert(1, s("one"));
+
+    let mut lru = BackedLru::new(2, store, WritePolicy::WriteThrough);
+    assert_eq!(lru.get(&1), Ok(Some(&s("one"))));
+    assert_eq!(lru.get(&1), Ok(Some(&s("one"))));
+    assert_eq!(lru.store().loads, 1);
+
+    // Misses are remembered too.
+    assert_eq!(lru.get(&2), Ok(None));
+    assert_eq!(lru.get(&2), Ok(None));
+    assert_eq!(lru.store().loads, 2);
+  }
+
+  #[test]
+  fn write_through() {
+    let mut lru = BackedLru::new(2, MemoryStore::default(), WritePolicy::WriteThrough);
+    lru.put(1, s("one")).unwrap();
+    assert_eq!(lru.store().data.get(&1), Some(&s("one")));
+
+    lru.delete(&1).unwrap();
+    assert_eq!(lru.store().data.get(&1), None);
+    assert_eq!(lru.get(&1), Ok(None));
+    assert_eq!(lru.store().loads, 0);
+
+    // A failed write doesn't reach the cache either.
+    lru.store_mut().fail = true;
+    assert_eq!(lru.put(2, s("two")), Err(s("can't store 2")));
+    lru.store_mut().fail = false;
+    assert_eq!(lru.get(&2), Ok(None));
+    assert_eq!(lru.store().loads, 1);
+  }
+
+  #[test]
+  fn write_behind() {
+    let mut lru = BackedLru::new(2, MemoryStore::default(), WritePolicy::WriteBehind);
+    lru.put(1, s("one")).unwrap();
+    lru.put(2, s("two")).unwrap();
+    lru.put(2, s("TWO")).unwrap();
+    assert_eq!(lru.store().writes, 0);
+
+    // Evicting 1 writes it out first.
+    lru.put(3, s("three")).unwrap();
+    assert_eq!(lru.store().writes, 1);
+    assert_eq!(lru.store().data.get(&1), Some(&s("one")));
+    assert_eq!(lru.store().data.get(&2), None);
+
+    // Deletes are deferred as well. This one evicts 2, which is
+    // written out with its latest value.
+    lru.store_mut().data.insert(4, s("four"));
+    lru.delete(&4).unwrap();
+    assert_eq!(lru.store().data.get(&2), Some(&s("TWO")));
+    assert_eq!(lru.store().data.get(&3), None);
+    assert_eq!(lru.store().data.get(&4), Some(&s("four")));
+
+    lru.flush().unwrap();
+    assert_eq!(lru.store().data.get(&3), Some(&s("three")));
+    assert_eq!(lru.store().data.get(&4), None);
+
+    lru.put(5, s("five")).unwrap();
+    lru.put(6, s("six")).unwrap();
+    lru.flush().unwrap();
+    assert_eq!(lru.store().data.get(&5), Some(&s("five")));
+    assert_eq!(lru.store().data.get(&6), Some(&s("six")));
+
+    // Flushing twice doesn't write anything again.
+    let writes = lru.store().writes;
+    lru.flush().unwrap();
+    assert_eq!(lru.store().writes, writes);
+  }
+
+  #[test]
+  fn failed_eviction_keeps_entry() {
+    let mut lru = BackedLru::new(1, MemoryStore::default(), WritePolicy::WriteBehind);
+    lru.put(1, s("one")).unwrap();
+
+    lru.store_mut().fail = true;
+    assert_eq!(lru.put(2, s("two")), Err(s("can't store 1")));
+    assert_eq!(lru.get(&1), Ok(Some(&s("one"))));
+
+    lru.store_mut().fail = false;
+    lru.put(2, s("two")).unwrap();
+    assert_eq!(lru.store().data.get(&1), Some(&s("one")));
+  }
+
+  // With no room, a write-behind put would never reach the store.
+  #[test]
+  #[should_panic(expected = "needs room for at least one entry")]
+  fn zero_capacity() {
+    BackedLru::new(0, MemoryStore::default(), WritePolicy::WriteBehind);
+  }
+
+  #[test]
+  fn flushes_on_drop() {
+    struct SharedStore(Rc<RefCell<HashMap<i32, String>>>);
+
+    impl BackingStore<i32, String> for SharedStore {
+      type Error = ();
+
+      fn load(&mut self, key: &i32) -> Result<Option<String>, ()> {
+        Ok(self.0.borrow().get(key).cloned())
+      }
+
+      fn store(&mut self, key: &i32, val: &String) -> Result<(), ()> {
+        self.0.borrow_mut().insert(*key, val.clone());
+        Ok(())
+      }
+
+      fn delete(&mut self, key: &i32) -> Result<(), ()> {
+        self.0.borrow_mut().remove(key);
+        Ok(())
+      }
+    }
+
+    let data = Rc::new(RefCell::new(HashMap::new()));
+    let mut lru = BackedLru::new(4, SharedStore(Rc::clone(&data)), WritePolicy::WriteBehind);
+    lru.put(1, s("one")).unwrap();
+    lru.put(2, s("two")).unwrap();
+    assert!(data.borrow().is_empty());
+
+    drop(lru);
+    assert_eq!(data.borrow().len(), 2);
+  }
+}
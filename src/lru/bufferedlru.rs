@@ -0,0 +1,329 @@
+use std::cell::UnsafeCell;
+use std::hash::Hash;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+use crate::lru::LRU;
+use crate::lru::veclru::VecLRU;
+
+
+// Once a read buffer holds this many keys, the reader that filled it
+// tries to replay it against the list.
+const DRAIN_THRESHOLD: usize = 32;
+
+// The size of each read buffer, a power of two. If the list stays
+// locked by writers for long enough to fill one, further reads are
+// simply not recorded, which only makes the recency order a little
+// less exact.
+const MAX_BUFFERED: usize = 256;
+
+static NEXT_STRIPE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+  // Each thread sticks to one stripe, so readers on different threads
+  // mostly push into different buffers.
+  static STRIPE: usize = NEXT_STRIPE.fetch_add(1, Ordering::Relaxed);
+}
+
+// A concurrent VecLRU where hits don't take the write lock, or any
+// lock of their own.
+//
+// A get takes the shared read lock just long enough to find and clone
+// the value; that is the only lock on the read path. Instead of calling
+// move_back right away, it records the key in its thread's read buffer,
+// a fixed ring that is pushed to with atomics alone. Those buffers are
+// replayed against the list in batches, under the write lock, either by
+// the reader that fills one up (if the lock is free) or by the next put.
+// This is the same trick Caffeine uses: the recency order lags a little
+// behind, but hits stop contending with each other on the list.
+//
+// The lookup itself still goes through the RwLock, since there is no
+// concurrent map underneath, so readers share that lock's counter.
+pub struct BufferedLru<K: Eq + Hash + Copy, T> {
+  lru: RwLock<VecLRU<K, T>>,
+  buffers: Vec<ReadBuffer<K>>,
+}
+
+// A bounded ring of keys that any number of readers push to without
+// locking, and that is only ever drained under the list's write lock.
+// Each slot carries a sequence number saying whose turn it is: a pusher
+// may fill slot i when its sequence is the position being pushed to,
+// and the drainer may take it once the pusher has bumped it by one.
+// Pushing to a full ring fails instead of waiting.
+struct ReadBuffer<K> {
+  slots: Box<[Slot<K>]>,
+  // Next position to push to, and next to drain from. Both only grow;
+  // the slot is the position modulo the ring size.
+  tail: AtomicUsize,
+  head: AtomicUsize,
+}
+
+struct Slot<K> {
+  seq: AtomicUsize,
+  key: UnsafeCell<MaybeUninit<K>>,
+}
+
+// A slot's key is only written by the one pusher that claimed it and
+// only read by the drainer after the pusher publishes it through seq.
+unsafe impl<K: Send> Sync for ReadBuffer<K> {}
+
+impl<K: Copy> ReadBuffer<K> {
+  fn new() -> Self {
+    ReadBuffer {
+      slots: (0..MAX_BUFFERED)
+        .map(|i| Slot { seq: AtomicUsize::new(i), key: UnsafeCell::new(MaybeUninit::uninit()) })
+        .collect(),
+      tail: AtomicUsize::new(0),
+      head: AtomicUsize::new(0),
+    }
+  }
+
+  // How many keys are waiting, give or take pushes in flight.
+  fn len(&self) -> usize {
+    let tail = self.tail.load(Ordering::Relaxed);
+    tail.wrapping_sub(self.head.load(Ordering::Relaxed))
+  }
+
+  fn push(&self, key: K) -> bool {
+    let mut pos = self.tail.load(Ordering::Relaxed);
+    loop {
+      let slot = &self.slots[pos % MAX_BUFFERED];
+      let seq = slot.seq.load(Ordering::Acquire);
+      match (seq as isize).wrapping_sub(pos as isize) {
+        // Free, claim it.
+        0 => {
+          let next = pos.wrapping_add(1);
+          match self.tail.compare_exchange_weak(pos, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => {
+              // SAFETY: winning the exchange makes this the only writer
+              // of the slot until seq says it is full.
+              unsafe { (*slot.key.get()).write(key) };
+              slot.seq.store(next, Ordering::Release);
+              return true;
+            },
+            Err(now) => pos = now,
+          }
+        },
+        // Still holds a key from a lap ago: full.
+        diff if diff < 0 => return false,
+        // Another pusher got here first.
+        _ => pos = self.tail.load(Ordering::Relaxed),
+      }
+    }
+  }
+
+  // Only called with the list's write lock held, so there is one
+  // drainer at a time. Stops at a slot whose pusher hasn't finished;
+  // that key gets picked up next time.
+  fn drain(&self, mut f: impl FnMut(K)) {
+    let mut pos = self.head.load(Ordering::Relaxed);
+    loop {
+      let slot = &self.slots[pos % MAX_BUFFERED];
+      if slot.seq.load(Ordering::Acquire) != pos.wrapping_add(1) {
+        break;
+      }
+
+      // SAFETY: seq says the pusher has written this slot and nobody
+      // else reads it until it is handed back below.
+      let key = unsafe { (*slot.key.get()).assume_init() };
+      slot.seq.store(pos.wrapping_add(MAX_BUFFERED), Ordering::Release);
+      pos = pos.wrapping_add(1);
+      self.head.store(pos, Ordering::Relaxed);
+      f(key);
+    }
+  }
+}
+
+impl<K: Eq + Hash + Copy, T: Clone> BufferedLru<K, T> {
+  pub fn new(capacity: usize) -> Self {
+    let stripes = std::thread::available_parallelism()
+      .map(|n| n.get())
+      .unwrap_or(1)
+      .next_power_of_two();
+
+    Self::with_stripes(capacity, stripes)
+  }
+
+  pub fn with_stripes(capacity: usize, stripes: usize) -> Self {
+    BufferedLru {
+      lru: RwLock::new(VecLRU::new(capacity)),
+      buffers: (0..stripes.max(1)).map(|_| ReadBuffer::new()).collect(),
+    }
+  }
+
+  fn buffer(&self) -> &ReadBuffer<K> {
+    let stripe = STRIPE.with(|s| *s);
+    &self.buffers[stripe % self.buffers.len()]
+  }
+
+  pub fn get(&self, key: &K) -> Option<T> {
+    let val = self.lru.read().unwrap().peek(key).as_deref().cloned()?;
+    self.record(*key);
+    Some(val)
+  }
+
+  pub fn put(&self, key: K, val: T) {
+    let mut lru = self.lru.write().unwrap();
+
+    // Reads that happened before this put have to land first, otherwise
+    // we could evict something that was just used.
+    for buffer in &self.buffers {
+      Self::replay(&mut lru, buffer);
+    }
+
+    lru.put(key, val);
+  }
+
+  // Applies every buffered read right now, instead of waiting for the
+  // next batch.
+  pub fn flush(&self) {
+    let mut lru = self.lru.write().unwrap();
+    for buffer in &self.buffers {
+      Self::replay(&mut lru, buffer);
+    }
+  }
+
+  fn record(&self, key: K) {
+    let buffer = self.buffer();
+    if !buffer.push(key) || buffer.len() < DRAIN_THRESHOLD {
+      return;
+    }
+
+    // Never wait here: if a writer holds the list, it will replay
+    // this buffer itself.
+    if let Ok(mut lru) = self.lru.try_write() {
+      Self::replay(&mut lru, buffer);
+    }
+  }
+
+  fn replay(lru: &mut VecLRU<K, T>, buffer: &ReadBuffer<K>) {
+    // Keys evicted since they were read are just misses here.
+    buffer.drain(|key| {
+      lru.get(&key);
+    });
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::sync::Arc;
+  use std::thread;
+
+  fn assert_send_sync<S: Send + Sync>() {}
+
+  #[test]
+  fn is_send_sync() {
+    assert_send_sync::<BufferedLru<i32, String>>();
+  }
+
+  #[test]
+  fn test() {
+    let lru = BufferedLru::with_stripes(3, 2);
+    lru.put(1, "one");
+    lru.put(2, "two");
+    lru.put(3, "three");
+
+    // The hit on 1 is only buffered, but the next put replays it before
+    // evicting, so 2 is the one that goes.
+    assert_eq!(lru.get(&1), Some("one"));
+    lru.put(4, "four");
+    assert_eq!(lru.get(&2), None);
+    assert_eq!(lru.get(&1), Some("one"));
+    assert_eq!(lru.get(&3), Some("three"));
+    assert_eq!(lru.get(&4), Some("four"));
+  }
+
+  #[test]
+  fn drains_full_buffer() {
+    let lru = BufferedLru::with_stripes(4, 1);
+    for i in 0..4 {
+      lru.put(i, i);
+    }
+
+    for _ in 0..DRAIN_THRESHOLD {
+      lru.get(&0);
+    }
+
+    // Filling the buffer replayed it without any put or flush.
+    assert_eq!(lru.buffers[0].len(), 0);
+    let order: Vec<i32> = lru.lru.write().unwrap()
+      .key_holder().iter().map(|(k, _)| *k).collect();
+    assert_eq!(order, vec![1, 2, 3, 0]);
+  }
+
+  #[test]
+  fn full_buffer_drops_reads() {
+    let lru = BufferedLru::with_stripes(4, 1);
+    lru.put(0, 0);
+    lru.put(1, 1);
+
+    // With a writer holding the list nothing gets replayed, so the
+    // ring fills up and the reads past that are let go.
+    let guard = lru.lru.write().unwrap();
+    for _ in 0..MAX_BUFFERED {
+      assert!(lru.buffers[0].push(0));
+    }
+    assert!(!lru.buffers[0].push(1));
+    assert_eq!(lru.buffers[0].len(), MAX_BUFFERED);
+    drop(guard);
+
+    lru.flush();
+    assert_eq!(lru.buffers[0].len(), 0);
+    let order: Vec<i32> = lru.lru.write().unwrap()
+      .key_holder().iter().map(|(k, _)| *k).collect();
+    assert_eq!(order, vec![1, 0]);
+
+    // The ring goes round again once drained.
+    assert!(lru.buffers[0].push(1));
+    lru.flush();
+    let order: Vec<i32> = lru.lru.write().unwrap()
+      .key_holder().iter().map(|(k, _)| *k).collect();
+    assert_eq!(order, vec![0, 1]);
+  }
+
+  #[test]
+  fn flush() {
+    let lru = BufferedLru::with_stripes(2, 4);
+    lru.put(1, 1);
+    lru.put(2, 2);
+    lru.get(&1);
+    lru.flush();
+
+    let order: Vec<i32> = lru.lru.write().unwrap()
+      .key_holder().iter().map(|(k, _)| *k).collect();
+    assert_eq!(order, vec![2, 1]);
+  }
+
+  #[test]
+  fn hammer() {
+    let lru = Arc::new(BufferedLru::with_stripes(128, 4));
+    for i in 0..128 {
+      lru.put(i, i * 3);
+    }
+
+    let handles: Vec<_> = (0..16).map(|t| {
+      let lru = Arc::clone(&lru);
+      thread::spawn(move || {
+        for i in 0..5000 {
+          let key = (t * 17 + i) % 256;
+          match lru.get(&key) {
+            Some(val) => assert_eq!(val, key * 3),
+            None if i % 10 == 0 => lru.put(key, key * 3),
+            None => {},
+          }
+        }
+      })
+    }).collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+
+    lru.flush();
+    let mut lru = lru.lru.write().unwrap();
+    let holder = lru.key_holder();
+    assert!(holder.size() <= 128);
+    assert_eq!(holder.iter().count(), holder.size());
+  }
+}
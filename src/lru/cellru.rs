@@ -1,33 +1,33 @@
-use std::collections::HashMap;
-use std::hash::Hash;
-use crate::lru::{LRU, KeyHolder};
-use crate::linked_list::{DLL, cellist};
-use std::marker::PhantomData;
+use crate::linked_list::cellist;
+use crate::lru::Lru;
+use crate::map::DefaultHashBuilder;
 
 
-pub struct CellLRU<K: Eq + Hash + Copy, T> {
-  key_holder: KeyHolder<K, T, cellist::CellLinkedList<(K, T)>>,
-}
+pub type CellLRU<K, T, S = DefaultHashBuilder> = Lru<K, T, cellist::CellLinkedList<(K, T)>, S>;
 
-impl<K: Eq + Hash + Copy, T> LRU<K, T> for CellLRU<K, T> {
-  type List = cellist::CellLinkedList<(K, T)>;
-
-  fn new(capacity: usize) -> Self {
-    CellLRU {
-      key_holder: KeyHolder {
-        hash: HashMap::new(),
-        list: cellist::CellLinkedList::new(),
-        _marker: PhantomData,
-        size: 0,
-        capacity: capacity,
-      },
-    }
-  }
 
-  fn key_holder(&mut self) -> &mut KeyHolder<K, T, cellist::CellLinkedList<(K, T)>> {
-    &mut self.key_holder
-  }
-}
+crate::lru::macros::lru_tests!(CellLRU);
 
+#[cfg(test)]
+crate::lru_conformance!(CellLRU<u32, u32>);
 
-crate::lru::macros::lru_tests!(CellLRU);
+
+#[cfg(test)]
+mod traits_test {
+  use super::*;
+  use crate::lru::LRU;
+
+  #[test]
+  fn clone_has_its_own_nodes() {
+    let mut lru: CellLRU<i32, i32> = (0..4).map(|i| (i, i * 10)).collect();
+    let mut copy = lru.clone();
+    assert_eq!(copy.key_holder().check_invariants(), Ok(()));
+
+    // Dropping the original leaves the clone's keys leading somewhere.
+    lru.remove(&0);
+    drop(lru);
+    assert_eq!(copy.get(&0).as_deref(), Some(&0));
+    copy.put(4, 40);
+    assert_eq!(format!("{copy:?}"), "{2: 20, 3: 30, 0: 0, 4: 40}");
+  }
+}
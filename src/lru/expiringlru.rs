@@ -0,0 +1,255 @@
+use std::hash::Hash;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::lru::LRU;
+use crate::lru::veclru::VecLRU;
+use crate::linked_list::DLL;
+
+
+// How many entries a sweep looks at before letting go of the lock, so
+// gets and puts never wait on one long sweep.
+const SWEEP_BATCH: usize = 64;
+
+// A cache shared between threads where every entry has a deadline.
+// Expired entries are never returned, and a sweeper thread can be
+// started to drop them without waiting for them to be evicted.
+//
+// Cloning an ExpiringLru gives another handle to the same cache.
+pub struct ExpiringLru<K: Eq + Hash + Copy, T> {
+  lru: Arc<Mutex<VecLRU<K, (T, Instant)>>>,
+}
+
+impl<K: Eq + Hash + Copy, T> Clone for ExpiringLru<K, T> {
+  fn clone(&self) -> Self {
+    ExpiringLru { lru: Arc::clone(&self.lru) }
+  }
+}
+
+impl<K: Eq + Hash + Copy, T: Clone> ExpiringLru<K, T> {
+  pub fn new(capacity: usize) -> Self {
+    ExpiringLru {
+      lru: Arc::new(Mutex::new(VecLRU::new(capacity))),
+    }
+  }
+
+  pub fn get(&self, key: &K) -> Option<T> {
+    let mut lru = self.lru.lock().unwrap();
+
+    // Check before get, so an expired entry is dropped instead of
+    // being moved to the most recently used end.
+    let deadline = lru.peek(key)?.1;
+    if deadline <= Instant::now() {
+      lru.remove(key);
+      return None;
+    }
+
+    lru.get(key).map(|entry| entry.0.clone())
+  }
+
+  pub fn put(&self, key: K, val: T, ttl: Duration) {
+    self.put_until(key, val, Instant::now() + ttl);
+  }
+
+  pub fn put_until(&self, key: K, val: T, deadline: Instant) {
+    self.lru.lock().unwrap().put(key, (val, deadline));
+  }
+
+  pub fn len(&self) -> usize {
+    self.lru.lock().unwrap().key_holder().size()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // Walks the cache from the least recently used end, drops every
+  // expired entry and returns how many were dropped. The lock is
+  // released every SWEEP_BATCH entries.
+  //
+  // If the entry the sweep was about to look at changed while the lock
+  // was released, the walk starts over from the front. It never looks
+  // at more entries than the cache held when it started.
+  pub fn sweep(&self) -> usize {
+    let mut budget = self.len();
+    let mut removed = 0;
+    let mut resume = None;
+
+    while budget > 0 {
+      let mut lru = self.lru.lock().unwrap();
+      let now = Instant::now();
+      let holder = lru.key_holder();
+
+      let mut curr = match resume.take() {
+        Some(ptr) if holder.list.get(&ptr).is_some() => Some(ptr),
+        _ => holder.list.head(),
+      };
+
+      for _ in 0..SWEEP_BATCH.min(budget) {
+        let ptr = match curr {
+          Some(ptr) => ptr,
+          None => return removed,
+        };
+        budget -= 1;
+        curr = holder.list.next_node(&ptr);
+
+        let expired_key = match holder.list.get(&ptr) {
+          Some((key, (_, deadline))) if *deadline <= now => *key,
+          _ => continue,
+        };
+
+        holder.list.remove(&ptr);
+        holder.hash.remove(&expired_key);
+        holder.size -= 1;
+        removed += 1;
+      }
+
+      resume = curr;
+      if resume.is_none() {
+        break;
+      }
+    }
+
+    removed
+  }
+}
+
+impl<K, T> ExpiringLru<K, T>
+where
+  K: Eq + Hash + Copy + Send + 'static,
+  T: Clone + Send + 'static,
+{
+  // Starts a thread that sweeps the cache every interval. The thread
+  // stops when the returned Sweeper is shut down or dropped.
+  pub fn start_sweeper(&self, interval: Duration) -> Sweeper {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let cache = self.clone();
+
+    let handle = thread::spawn(move || {
+      while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+        cache.sweep();
+      }
+    });
+
+    Sweeper {
+      stop: Some(stop),
+      handle: Some(handle),
+    }
+  }
+}
+
+// Handle to a running sweeper thread.
+pub struct Sweeper {
+  stop: Option<Sender<()>>,
+  handle: Option<JoinHandle<()>>,
+}
+
+impl Sweeper {
+  // Stops the sweeper and waits for its thread to exit. A sweep that
+  // is already running finishes first.
+  pub fn shutdown(mut self) {
+    self.stop_and_join();
+  }
+
+  fn stop_and_join(&mut self) {
+    // Dropping the sender wakes the thread up right away.
+    self.stop.take();
+    if let Some(handle) = self.handle.take() {
+      let _ = handle.join();
+    }
+  }
+}
+
+impl Drop for Sweeper {
+  fn drop(&mut self) {
+    self.stop_and_join();
+  }
+}
+
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test() {
+    let lru = ExpiringLru::new(3);
+    let now = Instant::now();
+
+    lru.put_until(1, "expired", now);
+    lru.put(2, "live", Duration::from_secs(60));
+    assert_eq!(lru.len(), 2);
+
+    assert_eq!(lru.sweep(), 1);
+    assert_eq!(lru.len(), 1);
+    assert_eq!(lru.get(&1), None);
+    assert_eq!(lru.get(&2), Some("live"));
+
+    // Putting again gives an expired key a new deadline.
+    lru.put_until(3, "soon", now);
+    assert_eq!(lru.get(&3), None);
+    lru.put(3, "later", Duration::from_secs(60));
+    assert_eq!(lru.get(&3), Some("later"));
+  }
+
+  #[test]
+  fn sweep_in_batches() {
+    let lru = ExpiringLru::new(1000);
+    let past = Instant::now();
+    lru.put(10_000, 0, Duration::from_secs(60));
+    for i in 0..(SWEEP_BATCH * 3 + 5) {
+      lru.put_until(i, i, past);
+    }
+
+    assert_eq!(lru.sweep(), SWEEP_BATCH * 3 + 5);
+    assert_eq!(lru.len(), 1);
+    assert_eq!(lru.sweep(), 0);
+  }
+
+  #[test]
+  fn sweep_skips_live_entries() {
+    let lru = ExpiringLru::new(5);
+    let now = Instant::now();
+    lru.put(1, 1, Duration::from_secs(60));
+    lru.put_until(2, 2, now);
+    lru.put(3, 3, Duration::from_secs(60));
+    lru.put_until(4, 4, now);
+
+    assert_eq!(lru.sweep(), 2);
+    assert_eq!(lru.len(), 2);
+    assert_eq!(lru.get(&1), Some(1));
+    assert_eq!(lru.get(&3), Some(3));
+  }
+
+  #[test]
+  fn get_drops_expired() {
+    let lru = ExpiringLru::new(2);
+    lru.put_until(1, 1, Instant::now());
+    assert_eq!(lru.len(), 1);
+    assert_eq!(lru.get(&1), None);
+    assert_eq!(lru.len(), 0);
+  }
+
+  #[test]
+  fn sweeper_thread() {
+    let lru = ExpiringLru::new(10);
+    for i in 0..5 {
+      lru.put(i, i, Duration::from_millis(20));
+    }
+
+    let sweeper = lru.start_sweeper(Duration::from_millis(5));
+    let start = Instant::now();
+    while !lru.is_empty() {
+      assert!(start.elapsed() < Duration::from_secs(5), "sweeper never ran");
+      thread::sleep(Duration::from_millis(5));
+    }
+
+    // Shutting down doesn't wait for the next tick.
+    let start = Instant::now();
+    let slow = lru.start_sweeper(Duration::from_secs(60));
+    slow.shutdown();
+    sweeper.shutdown();
+    assert!(start.elapsed() < Duration::from_secs(5));
+  }
+}
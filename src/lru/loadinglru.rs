@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex};
+use crate::lru::LRU;
+use crate::lru::veclru::VecLRU;
+
+
+enum FlightState<T, E> {
+  Loading,
+  Done(Result<T, E>),
+  // The loader panicked. Whoever was waiting starts over.
+  Abandoned,
+}
+
+// One in-progress load. The thread that created it runs the loader,
+// everyone else asking for the same key waits on it.
+struct Flight<T, E> {
+  state: Mutex<FlightState<T, E>>,
+  done: Condvar,
+}
+
+impl<T: Clone, E: Clone> Flight<T, E> {
+  fn new() -> Self {
+    Flight {
+      state: Mutex::new(FlightState::Loading),
+      done: Condvar::new(),
+    }
+  }
+
+  fn finish(&self, state: FlightState<T, E>) {
+    *self.state.lock().unwrap() = state;
+    self.done.notify_all();
+  }
+
+  // What the flight finished with, if it has.
+  fn result(&self) -> Option<Result<T, E>> {
+    match &*self.state.lock().unwrap() {
+      FlightState::Done(result) => Some(result.clone()),
+      _ => None,
+    }
+  }
+
+  // None means the load was abandoned.
+  fn wait(&self) -> Option<Result<T, E>> {
+    let mut state = self.state.lock().unwrap();
+    loop {
+      match &*state {
+        FlightState::Loading => state = self.done.wait(state).unwrap(),
+        FlightState::Done(result) => return Some(result.clone()),
+        FlightState::Abandoned => return None,
+      }
+    }
+  }
+}
+
+// A thread-safe read-through cache. A miss calls the loader, stores what
+// it returns and hands it back. While a key is loading, other callers
+// asking for the same key wait for that load instead of starting their
+// own, so the loader runs at most once per key at a time.
+//
+// Loader errors are returned to every waiting caller but are not cached,
+// so the next get tries again, unless cache_errors is turned on.
+pub struct LoadingLru<K: Eq + Hash + Copy, T, E, F> {
+  lru: Mutex<VecLRU<K, Result<T, E>>>,
+  in_flight: Mutex<HashMap<K, Arc<Flight<T, E>>>>,
+  loader: F,
+  cache_errors: bool,
+}
+
+impl<K, T, E, F> LoadingLru<K, T, E, F>
+where
+  K: Eq + Hash + Copy,
+  T: Clone,
+  E: Clone,
+  F: Fn(&K) -> Result<T, E>,
+{
+  pub fn new(capacity: usize, loader: F) -> Self {
+    LoadingLru {
+      lru: Mutex::new(VecLRU::new(capacity)),
+      in_flight: Mutex::new(HashMap::new()),
+      loader,
+      cache_errors: false,
+    }
+  }
+
+  // Keep loader errors in the cache like any other value, so a failing
+  // key isn't retried until it is evicted or overwritten.
+  pub fn cache_errors(mut self, cache_errors: bool) -> Self {
+    self.cache_errors = cache_errors;
+    self
+  }
+
+  pub fn get(&self, key: &K) -> Result<T, E> {
+    loop {
+      if let Some(result) = self.lru.lock().unwrap().get(key) {
+        return result.clone();
+      }
+
+      let flight = {
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        // A load may have finished between the check above and taking
+        // this lock. Loaders store their result before they leave
+        // in_flight, so looking again here can't miss it.
+        if let Some(result) = self.lru.lock().unwrap().get(key) {
+          return result.clone();
+        }
+
+        match in_flight.get(key) {
+          Some(flight) => Arc::clone(flight),
+          None => {
+            let flight = Arc::new(Flight::new());
+            in_flight.insert(*key, Arc::clone(&flight));
+            drop(in_flight);
+            return self.load(key, &flight);
+          },
+        }
+      };
+
+      if let Some(result) = flight.wait() {
+        return result;
+      }
+    }
+  }
+
+  // Puts a value in the cache directly, without calling the loader.
+  // If the key is loading, this value is newer than anything the loader
+  // can come back with, so the load's callers get it and the loader's
+  // result is thrown away.
+  pub fn put(&self, key: K, val: T) {
+    let in_flight = self.in_flight.lock().unwrap();
+    match in_flight.get(&key) {
+      Some(flight) => {
+        self.lru.lock().unwrap().put(key, Ok(val.clone()));
+        flight.finish(FlightState::Done(Ok(val)));
+      },
+      None => self.lru.lock().unwrap().put(key, Ok(val)),
+    }
+  }
+
+  fn load(&self, key: &K, flight: &Flight<T, E>) -> Result<T, E> {
+    // If the loader panics, waiters are told to retry and the key is
+    // free to be loaded again.
+    struct Abandon<'a, K: Eq + Hash, T: Clone, E: Clone> {
+      key: &'a K,
+      flight: &'a Flight<T, E>,
+      in_flight: &'a Mutex<HashMap<K, Arc<Flight<T, E>>>>,
+    }
+
+    impl<K: Eq + Hash, T: Clone, E: Clone> Drop for Abandon<'_, K, T, E> {
+      fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+          in_flight.remove(self.key);
+        }
+        self.flight.finish(FlightState::Abandoned);
+      }
+    }
+
+    let abandon = Abandon { key, flight, in_flight: &self.in_flight };
+    let result = (self.loader)(key);
+    std::mem::forget(abandon);
+
+    // Holding in_flight keeps a put from landing between the check and
+    // the store below.
+    let mut in_flight = self.in_flight.lock().unwrap();
+    in_flight.remove(key);
+    if let Some(newer) = flight.result() {
+      return newer;
+    }
+
+    if result.is_ok() || self.cache_errors {
+      self.lru.lock().unwrap().put(*key, result.clone());
+    }
+
+    drop(in_flight);
+    flight.finish(FlightState::Done(result.clone()));
+    result
+  }
+}
+
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::sync::Barrier;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::thread;
+  use std::time::Duration;
+
+  #[test]
+  fn test() {
+    let calls = AtomicUsize::new(0);
+    let lru = LoadingLru::new(2, |k: &i32| -> Result<i32, String> {
+      calls.fetch_add(1, Ordering::SeqCst);
+      Ok(k * 10)
+    });
+
+    assert_eq!(lru.get(&1), Ok(10));
+    assert_eq!(lru.get(&1), Ok(10));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    lru.put(2, 7);
+    assert_eq!(lru.get(&2), Ok(7));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // 1 is evicted, so it gets loaded again.
+    assert_eq!(lru.get(&3), Ok(30));
+    assert_eq!(lru.get(&1), Ok(10));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn errors() {
+    let calls = AtomicUsize::new(0);
+    let loader = |k: &i32| -> Result<i32, String> {
+      calls.fetch_add(1, Ordering::SeqCst);
+      Err(format!("no {k}"))
+    };
+
+    let lru = LoadingLru::new(2, &loader);
+    assert_eq!(lru.get(&1), Err(String::from("no 1")));
+    assert_eq!(lru.get(&1), Err(String::from("no 1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let lru = LoadingLru::new(2, &loader).cache_errors(true);
+    assert_eq!(lru.get(&1), Err(String::from("no 1")));
+    assert_eq!(lru.get(&1), Err(String::from("no 1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn single_flight() {
+    let calls = AtomicUsize::new(0);
+    let lru = LoadingLru::new(8, |k: &i32| -> Result<i32, ()> {
+      calls.fetch_add(1, Ordering::SeqCst);
+      thread::sleep(Duration::from_millis(50));
+      Ok(k + 1)
+    });
+
+    thread::scope(|s| {
+      for _ in 0..16 {
+        s.spawn(|| assert_eq!(lru.get(&5), Ok(6)));
+      }
+    });
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn put_during_load_wins() {
+    let barrier = Barrier::new(2);
+    let lru = LoadingLru::new(8, |_: &i32| -> Result<i32, ()> {
+      // Let the put in while the load is still running.
+      barrier.wait();
+      barrier.wait();
+      Ok(1)
+    });
+
+    thread::scope(|s| {
+      let loading = s.spawn(|| lru.get(&1));
+      let writer = s.spawn(|| {
+        barrier.wait();
+        lru.put(1, 100);
+        barrier.wait();
+      });
+
+      writer.join().unwrap();
+      assert_eq!(loading.join().unwrap(), Ok(100));
+    });
+
+    assert_eq!(lru.get(&1), Ok(100));
+  }
+
+  #[test]
+  fn loader_panics() {
+    let calls = AtomicUsize::new(0);
+    let lru = LoadingLru::new(8, |k: &i32| -> Result<i32, ()> {
+      if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(50));
+        panic!("first load fails");
+      }
+      Ok(*k)
+    });
+
+    thread::scope(|s| {
+      let first = s.spawn(|| lru.get(&1));
+      thread::sleep(Duration::from_millis(10));
+      let second = s.spawn(|| lru.get(&1));
+
+      assert!(first.join().is_err());
+      assert_eq!(second.join().unwrap(), Ok(1));
+    });
+  }
+}
@@ -0,0 +1,12 @@
+use crate::linked_list::ptrlist;
+use crate::lru::Lru;
+use crate::map::DefaultHashBuilder;
+
+
+pub type PtrLRU<K, T, S = DefaultHashBuilder> = Lru<K, T, ptrlist::PtrLinkedList<(K, T)>, S>;
+
+
+crate::lru::macros::lru_tests!(PtrLRU);
+
+#[cfg(test)]
+crate::lru_conformance!(PtrLRU<u32, u32>);
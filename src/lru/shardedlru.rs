@@ -0,0 +1,198 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Mutex;
+use std::thread;
+use crate::lru::LRU;
+use crate::lru::veclru::VecLRU;
+
+
+// A concurrent cache made of several independent VecLRUs. Each key is
+// hashed to exactly one shard, and each shard has its own lock, so
+// threads working on different shards never wait on each other.
+//
+// Recency is tracked per shard: an entry is evicted when it is the least
+// recently used entry *of its shard*, which is only an approximation of
+// the global LRU order.
+pub struct ShardedLru<K: Eq + Hash + Copy, T> {
+  shards: Vec<Mutex<VecLRU<K, T>>>,
+  hasher: RandomState,
+  capacity: usize,
+}
+
+impl<K: Eq + Hash + Copy, T: Clone> ShardedLru<K, T> {
+  // Picks a shard count from the number of cores on this machine.
+  pub fn new(capacity: usize) -> Self {
+    let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    Self::with_shards(capacity, (cores * 4).next_power_of_two())
+  }
+
+  // The capacity is split as evenly as possible across the shards. There
+  // are never more shards than capacity, so every shard holds at least
+  // one entry.
+  pub fn with_shards(capacity: usize, shards: usize) -> Self {
+    assert!(capacity > 0, "ShardedLru needs a capacity of at least 1");
+    let count = shards.clamp(1, capacity);
+
+    let shards = (0..count).map(|i| {
+      let shard_capacity = capacity / count + usize::from(i < capacity % count);
+      Mutex::new(VecLRU::new(shard_capacity))
+    }).collect();
+
+    ShardedLru {
+      shards,
+      hasher: RandomState::new(),
+      capacity,
+    }
+  }
+
+  fn shard(&self, key: &K) -> &Mutex<VecLRU<K, T>> {
+    let index = self.hasher.hash_one(key) as usize % self.shards.len();
+    &self.shards[index]
+  }
+
+  pub fn get(&self, key: &K) -> Option<T> {
+    self.shard(key).lock().unwrap().get(key).as_deref().cloned()
+  }
+
+  pub fn put(&self, key: K, val: T) {
+    self.shard(&key).lock().unwrap().put(key, val);
+  }
+
+  pub fn shard_count(&self) -> usize {
+    self.shards.len()
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  // Shards are locked one after the other, so under concurrent writes
+  // this is a sum of per-shard snapshots rather than one atomic count.
+  pub fn len(&self) -> usize {
+    self.shards.iter()
+      .map(|shard| shard.lock().unwrap().key_holder().size())
+      .sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // Visits every entry, shard by shard, from least to most recently used
+  // within each shard. It works on a copy of the entries, so no lock is
+  // held while f runs, and f is free to use the cache itself.
+  pub fn for_each<F: FnMut(&K, &T)>(&self, mut f: F) {
+    for (key, val) in self.entries() {
+      f(&key, &val);
+    }
+  }
+
+  // A cloned copy of every entry, in the same order as for_each. Only
+  // one shard is locked at a time, and only while it is copied.
+  pub fn entries(&self) -> Vec<(K, T)> {
+    let mut entries = Vec::new();
+    for shard in &self.shards {
+      let mut lru = shard.lock().unwrap();
+      entries.extend(lru.key_holder().iter().map(|entry| (entry.0, entry.1.clone())));
+    }
+    entries
+  }
+}
+
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::collections::HashSet;
+  use std::sync::Arc;
+
+  #[test]
+  fn splits_capacity() {
+    let lru: ShardedLru<i32, i32> = ShardedLru::with_shards(10, 4);
+    assert_eq!(lru.shard_count(), 4);
+    assert_eq!(lru.capacity(), 10);
+
+    let capacities: Vec<usize> = lru.shards.iter()
+      .map(|s| s.lock().unwrap().key_holder().capacity())
+      .collect();
+    assert_eq!(capacities, vec![3, 3, 2, 2]);
+
+    // Never more shards than slots.
+    let small: ShardedLru<i32, i32> = ShardedLru::with_shards(3, 16);
+    assert_eq!(small.shard_count(), 3);
+  }
+
+  #[test]
+  fn test() {
+    let lru = ShardedLru::with_shards(100, 4);
+    assert!(lru.is_empty());
+
+    for i in 0..50 {
+      lru.put(i, i * 10);
+    }
+    assert_eq!(lru.len(), 50);
+
+    for i in 0..50 {
+      assert_eq!(lru.get(&i), Some(i * 10));
+    }
+
+    let keys: HashSet<i32> = lru.entries().into_iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, (0..50).collect());
+
+    // Never holds more than its capacity, no matter how keys spread.
+    for i in 0..1000 {
+      lru.put(i, i);
+    }
+    assert!(lru.len() <= lru.capacity());
+
+    let mut visited = 0;
+    lru.for_each(|k, v| {
+      assert_eq!(k, v);
+      visited += 1;
+    });
+    assert_eq!(visited, lru.len());
+  }
+
+  #[test]
+  fn for_each_can_use_the_cache() {
+    let lru = ShardedLru::with_shards(100, 2);
+    for i in 0..8 {
+      lru.put(i, i * 10);
+    }
+
+    let mut seen = 0;
+    lru.for_each(|k, v| {
+      assert_eq!(lru.get(k), Some(*v));
+      lru.put(*k, *v + 1);
+      seen += 1;
+    });
+    assert_eq!(seen, 8);
+    assert_eq!(lru.len(), 8);
+    assert_eq!(lru.get(&3), Some(31));
+  }
+
+  #[test]
+  fn hammer() {
+    let lru = Arc::new(ShardedLru::with_shards(256, 8));
+
+    let handles: Vec<_> = (0..16).map(|t| {
+      let lru = Arc::clone(&lru);
+      thread::spawn(move || {
+        for i in 0..2000 {
+          let key = (t * 31 + i) % 500;
+          lru.put(key, key + 1);
+          if let Some(val) = lru.get(&key) {
+            assert_eq!(val, key + 1);
+          }
+        }
+      })
+    }).collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+
+    assert!(lru.len() <= 256);
+    lru.for_each(|k, v| assert_eq!(*v, *k + 1));
+  }
+}
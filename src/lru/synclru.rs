@@ -0,0 +1,97 @@
+use std::hash::Hash;
+use std::sync::Mutex;
+use crate::lru::LRU;
+use crate::lru::veclru::VecLRU;
+
+
+// A VecLRU that can be shared between threads. Every call takes the
+// lock for the duration of one LRU operation, so the methods only need
+// &self. Values come back cloned, since a reference can't outlive the
+// lock guard; store an Arc<T> if cloning T is expensive.
+pub struct SyncLru<K: Eq + Hash + Copy, T> {
+  lru: Mutex<VecLRU<K, T>>,
+}
+
+impl<K: Eq + Hash + Copy, T: Clone> SyncLru<K, T> {
+  pub fn new(capacity: usize) -> Self {
+    SyncLru {
+      lru: Mutex::new(VecLRU::new(capacity)),
+    }
+  }
+
+  pub fn get(&self, key: &K) -> Option<T> {
+    self.lru.lock().unwrap().get(key).as_deref().cloned()
+  }
+
+  pub fn put(&self, key: K, val: T) {
+    self.lru.lock().unwrap().put(key, val);
+  }
+}
+
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::sync::Arc;
+  use std::thread;
+
+  fn assert_send_sync<S: Send + Sync>() {}
+
+  #[test]
+  fn is_send_sync() {
+    assert_send_sync::<SyncLru<i32, String>>();
+    assert_send_sync::<SyncLru<u64, Arc<Vec<u8>>>>();
+  }
+
+  #[test]
+  fn test() {
+    let lru = SyncLru::new(2);
+    assert_eq!(lru.get(&1), None);
+
+    lru.put(1, String::from("one"));
+    lru.put(2, String::from("two"));
+    assert_eq!(lru.get(&1), Some(String::from("one")));
+
+    // 2 is now the least recently used
+    lru.put(3, String::from("three"));
+    assert_eq!(lru.get(&2), None);
+    assert_eq!(lru.get(&1), Some(String::from("one")));
+    assert_eq!(lru.get(&3), Some(String::from("three")));
+  }
+
+  #[test]
+  fn hammer() {
+    let threads = 16;
+    let per_thread = 2000;
+    let lru = Arc::new(SyncLru::new(64));
+
+    let handles: Vec<_> = (0..threads).map(|t| {
+      let lru = Arc::clone(&lru);
+      thread::spawn(move || {
+        for i in 0..per_thread {
+          let key = (t * per_thread + i) % 100;
+          lru.put(key, Arc::new(key * 2));
+
+          // Whatever we read back must be the value written for that key,
+          // even if another thread evicted or re-inserted it in between.
+          if let Some(val) = lru.get(&key) {
+            assert_eq!(*val, key * 2);
+          }
+        }
+      })
+    }).collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+
+    // Still behaves like an LRU after all of that.
+    for key in 0..64 {
+      lru.put(1000 + key, Arc::new(key));
+    }
+    for key in 0..64 {
+      assert_eq!(lru.get(&(1000 + key)).as_deref(), Some(&key));
+    }
+    assert_eq!(lru.get(&0), None);
+  }
+}
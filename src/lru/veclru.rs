@@ -1,33 +1,221 @@
-use std::collections::HashMap;
-use std::hash::Hash;
-use crate::lru::{LRU, KeyHolder};
-use crate::linked_list::{DLL, veclist};
-use std::marker::PhantomData;
+use core::hash::{BuildHasher, Hash};
+use crate::lru::Lru;
+use crate::linked_list::veclist;
+use crate::map::DefaultHashBuilder;
 
 
-pub struct VecLRU<K: Eq + Hash + Copy, T> {
-  key_holder: KeyHolder<K, T, veclist::VectorLinkedList<(K, T)>>,
+pub type VecLRU<K, T, S = DefaultHashBuilder> = Lru<K, T, veclist::VectorLinkedList<(K, T)>, S>;
+
+impl<K: Eq + Hash + Copy, T, S: BuildHasher> VecLRU<K, T, S> {
+  // Packs the entries into the front of the list's spine and frees the
+  // memory left over from removals.
+  pub fn shrink_to_fit(&mut self) {
+    let holder = &mut self.key_holder;
+    let remap = holder.list.shrink_to_fit();
+    for ptr in holder.hash.values_mut() {
+      *ptr = remap[ptr];
+    }
+    holder.hash.shrink_to_fit();
+  }
+}
+
+
+crate::lru::macros::lru_tests!(VecLRU);
+
+#[cfg(test)]
+crate::lru_conformance!(VecLRU<u32, u32>);
+
+
+#[cfg(test)]
+mod shrink_test {
+  use super::*;
+  use crate::lru::LRU;
+
+  #[test]
+  fn shrink_to_fit() {
+    let mut lru = VecLRU::new(100);
+    for i in 0..100 {
+      lru.put(i, i * 10);
+    }
+    for i in 0..95 {
+      lru.remove(&i);
+    }
+    lru.get(&96);
+
+    lru.shrink_to_fit();
+    assert_eq!(lru.key_holder().size(), 5);
+    assert_eq!(lru.peek(&95).as_deref(), Some(&950));
+    assert_eq!(lru.get(&99).as_deref(), Some(&990));
+
+    // Recency survives: 97 is the least recently used now.
+    let order: Vec<_> = lru.key_holder().iter().map(|(k, _)| *k).collect();
+    assert_eq!(order, vec![95, 97, 98, 96, 99]);
+
+    lru.put(200, 2000);
+    assert_eq!(lru.remove(&98), Some(980));
+    assert_eq!(lru.get(&200).as_deref(), Some(&2000));
+  }
+}
+
+
+#[cfg(test)]
+mod hasher_test {
+  use super::*;
+  use crate::lru::LRU;
+  use crate::map::FxBuildHasher;
+  use core::hash::{BuildHasherDefault, Hasher};
+
+  // Hashes an integer key to itself.
+  #[derive(Default)]
+  struct IdentityHasher(u64);
+
+  impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+      self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+      unreachable!("only for integer keys")
+    }
+
+    fn write_u64(&mut self, n: u64) {
+      self.0 = n;
+    }
+  }
+
+  #[test]
+  fn fx_hasher() {
+    let mut lru = VecLRU::with_hasher(2, FxBuildHasher::default());
+    lru.put(1, "a");
+    lru.put(2, "b");
+    lru.get(&1);
+    lru.put(3, "c");
+    assert_eq!(lru.peek(&1).as_deref(), Some(&"a"));
+    assert!(lru.peek(&2).is_none());
+    assert_eq!(lru.get(&3).as_deref(), Some(&"c"));
+  }
+
+  #[test]
+  fn identity_hasher() {
+    type Identity = BuildHasherDefault<IdentityHasher>;
+    let mut lru = VecLRU::with_capacity_and_hasher(100, Identity::default());
+    for i in 0..200u64 {
+      lru.put(i, i * 2);
+    }
+    assert_eq!(lru.key_holder().size(), 100);
+    assert!(lru.peek(&99).is_none());
+    assert_eq!(lru.get(&100).as_deref(), Some(&200));
+
+    // LRU::new works for any hasher with a Default.
+    let mut other: VecLRU<u64, u64, Identity> = LRU::new(1);
+    other.put(7, 8);
+    assert_eq!(other.remove(&7), Some(8));
+  }
 }
 
-impl<K: Eq + Hash + Copy, T> LRU<K, T> for VecLRU<K, T> {
-  type List = veclist::VectorLinkedList<(K, T)>;
 
-  fn new(capacity: usize) -> Self {
-    VecLRU {
-      key_holder: KeyHolder {
-        hash: HashMap::new(),
-        list: veclist::VectorLinkedList::new(),
-        _marker: PhantomData,
-        size: 0,
-        capacity: capacity,
-      },
+#[cfg(test)]
+mod invariant_test {
+  use super::*;
+  use crate::linked_list::{DLL, InvariantError};
+  use crate::lru::LRU;
+
+  fn full_lru() -> VecLRU<i32, i32> {
+    let mut lru = VecLRU::new(3);
+    for i in 0..4 {
+      lru.put(i, i * 10);
     }
+    lru
   }
 
-  fn key_holder(&mut self) -> &mut KeyHolder<K, T, veclist::VectorLinkedList<(K, T)>> {
-    &mut self.key_holder
+  #[test]
+  fn key_holder() {
+    let mut lru = full_lru();
+    assert_eq!(lru.key_holder().check_invariants(), Ok(()));
+
+    let holder = lru.key_holder();
+    let ptr = holder.hash[&1];
+    holder.hash.insert(9, ptr);
+    assert_eq!(
+      holder.check_invariants(),
+      Err(InvariantError::KeyCountMismatch { keys: 4, entries: 3 }),
+    );
+    holder.hash.remove(&1);
+    assert_eq!(holder.check_invariants(), Err(InvariantError::WrongKey));
+
+    let mut lru = full_lru();
+    let holder = lru.key_holder();
+    let ptr = holder.hash[&1];
+    holder.list.remove(&ptr);
+    holder.list.push_back((1, 10));
+    assert_eq!(holder.check_invariants(), Err(InvariantError::StaleKey));
+
+    let mut lru = full_lru();
+    let holder = lru.key_holder();
+    holder.capacity = 2;
+    assert_eq!(holder.check_invariants(), Err(InvariantError::OverCapacity { size: 3, capacity: 2 }));
+    holder.size = 2;
+    assert_eq!(
+      holder.check_invariants(),
+      Err(InvariantError::SizeMismatch { size: 2, reachable: 3 }),
+    );
   }
 }
 
+#[cfg(test)]
+mod traits_test {
+  use super::*;
+  use crate::lru::LRU;
 
-crate::lru::macros::lru_tests!(VecLRU);
+  #[test]
+  fn std_traits() {
+    let mut lru: VecLRU<i32, &str> = [(1, "a"), (2, "b"), (3, "c")].into_iter().collect();
+    assert_eq!(lru.key_holder().capacity(), 3);
+    assert_eq!(format!("{lru:?}"), r#"{1: "a", 2: "b", 3: "c"}"#);
+
+    // The clone is separate, with the same recency order.
+    lru.get(&1);
+    let mut copy = lru.clone();
+    copy.put(4, "d");
+    assert_eq!(format!("{copy:?}"), r#"{3: "c", 1: "a", 4: "d"}"#);
+    assert_eq!(format!("{lru:?}"), r#"{2: "b", 3: "c", 1: "a"}"#);
+    assert_eq!(copy.key_holder().check_invariants(), Ok(()));
+
+    lru.extend([(5, "e"), (2, "f")]);
+    assert_eq!(format!("{lru:?}"), r#"{1: "a", 5: "e", 2: "f"}"#);
+  }
+
+  #[test]
+  fn from_iter_capacity() {
+    // Repeated keys only count once.
+    let lru: VecLRU<i32, &str> = [(1, "a"), (1, "b"), (2, "c")].into_iter().collect();
+    assert_eq!(lru.key_holder.capacity(), 2);
+    assert_eq!(format!("{lru:?}"), r#"{1: "b", 2: "c"}"#);
+
+    // Nothing in still leaves room for something.
+    let mut lru: VecLRU<i32, &str> = core::iter::empty().collect();
+    assert_eq!(lru.key_holder().capacity(), 1);
+    lru.put(1, "a");
+    assert_eq!(lru.get(&1).as_deref(), Some(&"a"));
+  }
+
+  // A hasher with no Default, which LRU can't build but extend can use.
+  struct Seeded(u64);
+
+  impl core::hash::BuildHasher for Seeded {
+    type Hasher = crate::map::FxHasher;
+    fn build_hasher(&self) -> Self::Hasher {
+      let mut hasher = crate::map::FxHasher::default();
+      core::hash::Hasher::write_u64(&mut hasher, self.0);
+      hasher
+    }
+  }
+
+  #[test]
+  fn extend_with_any_hasher() {
+    let mut lru: VecLRU<i32, &str, Seeded> = VecLRU::with_hasher(2, Seeded(7));
+    lru.extend([(1, "a"), (2, "b"), (3, "c")]);
+    assert_eq!(format!("{lru:?}"), r#"{2: "b", 3: "c"}"#);
+    assert_eq!(lru.key_holder.check_invariants(), Ok(()));
+  }
+}
@@ -0,0 +1,74 @@
+use core::hash::{BuildHasherDefault, Hasher};
+
+// Built for tests either way, so it gets tested with std on too.
+#[cfg(any(not(feature = "std"), test))]
+#[cfg_attr(feature = "std", allow(dead_code))]
+mod allocmap;
+
+// The map KeyHolder keeps its pointers in. With std it is std's own
+// HashMap. Without it, it is AllocHashMap, which only needs alloc and
+// takes any BuildHasher the same way.
+#[cfg(feature = "std")]
+pub use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+pub use self::allocmap::AllocHashMap as HashMap;
+
+// std's RandomState seeds SipHash from the OS, so it is safe against
+// keys picked to collide. Without std there is no randomness to seed
+// from, so the default is FxHasher. If your keys come from somewhere
+// you don't trust, bring your own BuildHasher.
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+#[cfg(not(feature = "std"))]
+pub type DefaultHashBuilder = FxBuildHasher;
+
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+// The hash rustc uses: one rotate, xor and multiply per word. Very fast
+// for small keys like integers, but easy to make collide on purpose.
+#[derive(Clone, Copy, Default)]
+pub struct FxHasher {
+  hash: u64,
+}
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl FxHasher {
+  fn add(&mut self, word: u64) {
+    self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+  }
+}
+
+impl Hasher for FxHasher {
+  fn write(&mut self, bytes: &[u8]) {
+    for chunk in bytes.chunks(8) {
+      let mut word = [0; 8];
+      word[..chunk.len()].copy_from_slice(chunk);
+      self.add(u64::from_le_bytes(word));
+    }
+  }
+
+  fn write_u8(&mut self, i: u8) {
+    self.add(i as u64);
+  }
+
+  fn write_u16(&mut self, i: u16) {
+    self.add(i as u64);
+  }
+
+  fn write_u32(&mut self, i: u32) {
+    self.add(i as u64);
+  }
+
+  fn write_u64(&mut self, i: u64) {
+    self.add(i);
+  }
+
+  fn write_usize(&mut self, i: usize) {
+    self.add(i as u64);
+  }
+
+  fn finish(&self) -> u64 {
+    self.hash
+  }
+}
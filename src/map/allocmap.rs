@@ -0,0 +1,358 @@
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use core::ops::Index;
+use crate::map::DefaultHashBuilder;
+
+
+// Never let the table get fuller than this, so probe runs stay short.
+const MAX_LOAD_NUM: usize = 3;
+const MAX_LOAD_DEN: usize = 4;
+const MIN_SLOTS: usize = 8;
+
+// A HashMap that only needs alloc, for builds without std. It has the
+// part of std's HashMap API this crate uses.
+//
+// Linear probing over a power of two table. Slots are picked from the
+// top bits of the hash after mixing it once more, so a weak hasher
+// still spreads keys out: an identity hasher leaves the top bits of
+// small integers all zero, which would pile every key into one run.
+// Removing shifts the rest of the run back instead of leaving
+// tombstones, so the table never fills up with them.
+#[derive(Clone)]
+pub struct AllocHashMap<K, V, S = DefaultHashBuilder> {
+  slots: Vec<Option<(K, V)>>,
+  len: usize,
+  hash_builder: S,
+}
+
+impl<K, V, S: Default> AllocHashMap<K, V, S> {
+  pub fn new() -> Self {
+    Self::with_hasher(S::default())
+  }
+
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self::with_capacity_and_hasher(capacity, S::default())
+  }
+}
+
+impl<K, V, S: Default> Default for AllocHashMap<K, V, S> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<K, V, S> AllocHashMap<K, V, S> {
+  pub fn with_hasher(hash_builder: S) -> Self {
+    AllocHashMap { slots: Vec::new(), len: 0, hash_builder }
+  }
+
+  pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+    let mut map = Self::with_hasher(hash_builder);
+    if capacity > 0 {
+      map.slots = empty_slots(slots_for(capacity));
+    }
+    map
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.slots.len() * MAX_LOAD_NUM / MAX_LOAD_DEN
+  }
+
+  pub fn hasher(&self) -> &S {
+    &self.hash_builder
+  }
+
+  pub fn clear(&mut self) {
+    self.slots.iter_mut().for_each(|slot| *slot = None);
+    self.len = 0;
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+    self.slots.iter().filter_map(|slot| slot.as_ref().map(|(k, v)| (k, v)))
+  }
+
+  pub fn keys(&self) -> impl Iterator<Item = &K> {
+    self.iter().map(|(k, _)| k)
+  }
+
+  pub fn values(&self) -> impl Iterator<Item = &V> {
+    self.iter().map(|(_, v)| v)
+  }
+
+  pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+    self.slots.iter_mut().filter_map(|slot| slot.as_mut().map(|(_, v)| v))
+  }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> AllocHashMap<K, V, S> {
+  pub fn get(&self, key: &K) -> Option<&V> {
+    let i = self.find(key)?;
+    self.slots[i].as_ref().map(|(_, v)| v)
+  }
+
+  pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+    let i = self.find(key)?;
+    self.slots[i].as_mut().map(|(_, v)| v)
+  }
+
+  pub fn contains_key(&self, key: &K) -> bool {
+    self.find(key).is_some()
+  }
+
+  pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+    if let Some(i) = self.find(&key) {
+      return self.slots[i].as_mut().map(|(_, v)| mem::replace(v, val));
+    }
+
+    self.reserve(1);
+    let mut i = self.ideal_slot(&key);
+    while self.slots[i].is_some() {
+      i = self.next_slot(i);
+    }
+    self.slots[i] = Some((key, val));
+    self.len += 1;
+    None
+  }
+
+  pub fn remove(&mut self, key: &K) -> Option<V> {
+    let mut hole = self.find(key)?;
+    let (_, val) = self.slots[hole].take()?;
+    self.len -= 1;
+
+    // Pull later entries of the same run back into the hole, as long as
+    // that doesn't move them in front of the slot they hash to.
+    let mut i = self.next_slot(hole);
+    while let Some((k, _)) = &self.slots[i] {
+      let ideal = self.ideal_slot(k);
+      if self.distance(ideal, i) >= self.distance(hole, i) {
+        self.slots[hole] = self.slots[i].take();
+        hole = i;
+      }
+      i = self.next_slot(i);
+    }
+
+    Some(val)
+  }
+
+  pub fn reserve(&mut self, additional: usize) {
+    let needed = self.len + additional;
+    if needed * MAX_LOAD_DEN > self.slots.len() * MAX_LOAD_NUM {
+      self.resize(slots_for(needed));
+    }
+  }
+
+  pub fn shrink_to_fit(&mut self) {
+    let slots = if self.len == 0 { 0 } else { slots_for(self.len) };
+    if slots < self.slots.len() {
+      self.resize(slots);
+    }
+  }
+
+  fn resize(&mut self, slots: usize) {
+    let old = mem::replace(&mut self.slots, empty_slots(slots));
+    for (key, val) in old.into_iter().flatten() {
+      let mut i = self.ideal_slot(&key);
+      while self.slots[i].is_some() {
+        i = self.next_slot(i);
+      }
+      self.slots[i] = Some((key, val));
+    }
+  }
+
+  fn find(&self, key: &K) -> Option<usize> {
+    if self.slots.is_empty() {
+      return None;
+    }
+
+    // The load limit means there is always an empty slot to stop at.
+    let mut i = self.ideal_slot(key);
+    loop {
+      match &self.slots[i] {
+        None => return None,
+        Some((k, _)) if k == key => return Some(i),
+        Some(_) => i = self.next_slot(i),
+      }
+    }
+  }
+
+  fn ideal_slot(&self, key: &K) -> usize {
+    let bits = self.slots.len().trailing_zeros();
+    let hash = self.hash_builder.hash_one(key);
+    // Folds the low bits into the high ones, then a Fibonacci multiply
+    // spreads them over the top bits the slot is taken from.
+    ((hash ^ (hash >> 32)).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - bits)) as usize
+  }
+
+  fn next_slot(&self, i: usize) -> usize {
+    (i + 1) & (self.slots.len() - 1)
+  }
+
+  // How many steps it takes to probe from a to b.
+  fn distance(&self, a: usize, b: usize) -> usize {
+    b.wrapping_sub(a) & (self.slots.len() - 1)
+  }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> Index<&K> for AllocHashMap<K, V, S> {
+  type Output = V;
+
+  fn index(&self, key: &K) -> &V {
+    self.get(key).expect("key not in map")
+  }
+}
+
+// The smallest power of two table that holds n entries under the load
+// limit.
+fn slots_for(n: usize) -> usize {
+  (n * MAX_LOAD_DEN).div_ceil(MAX_LOAD_NUM).next_power_of_two().max(MIN_SLOTS)
+}
+
+fn empty_slots<K, V>(n: usize) -> Vec<Option<(K, V)>> {
+  let mut slots = Vec::with_capacity(n);
+  slots.resize_with(n, || None);
+  slots
+}
+
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::map::FxBuildHasher;
+  use std::collections::hash_map::RandomState;
+
+  #[test]
+  fn test() {
+    let mut map: AllocHashMap<i32, &str> = AllocHashMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.get(&1), None);
+    assert_eq!(map.remove(&1), None);
+
+    assert_eq!(map.insert(1, "one"), None);
+    assert_eq!(map.insert(2, "two"), None);
+    assert_eq!(map.insert(1, "uno"), Some("one"));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&1], "uno");
+    assert!(map.contains_key(&2));
+
+    *map.get_mut(&2).unwrap() = "dos";
+    assert_eq!(map.get(&2), Some(&"dos"));
+    assert_eq!(map.remove(&2), Some("dos"));
+    assert_eq!(map.remove(&2), None);
+    assert_eq!(map.len(), 1);
+
+    map.clear();
+    assert!(map.is_empty());
+    assert_eq!(map.get(&1), None);
+  }
+
+  // Keys that all land in the same slot make the longest runs, which
+  // is where removing has the most to shift back.
+  #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+  struct Clash(u32);
+
+  impl Hash for Clash {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+      state.write_u32(self.0 % 4);
+    }
+  }
+
+  #[test]
+  fn collisions() {
+    let mut map: AllocHashMap<Clash, u32, FxBuildHasher> = AllocHashMap::new();
+    for i in 0..100 {
+      map.insert(Clash(i), i);
+    }
+    for i in (0..100).step_by(3) {
+      assert_eq!(map.remove(&Clash(i)), Some(i));
+    }
+    for i in 0..100 {
+      let expected = if i % 3 == 0 { None } else { Some(&i) };
+      assert_eq!(map.get(&Clash(i)), expected);
+    }
+    assert_eq!(map.len(), 66);
+  }
+
+  // Hashes an integer key to itself, which leaves every small key with
+  // the same top bits.
+  #[derive(Default)]
+  struct IdentityHasher(u64);
+
+  impl core::hash::Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+      self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+      unreachable!("only for integer keys")
+    }
+
+    fn write_u64(&mut self, n: u64) {
+      self.0 = n;
+    }
+  }
+
+  // The most steps any entry is from the slot it hashes to.
+  fn longest_probe<K: Eq + Hash, V, S: BuildHasher>(map: &AllocHashMap<K, V, S>) -> usize {
+    map.slots.iter().enumerate()
+      .filter_map(|(i, slot)| slot.as_ref().map(|(k, _)| map.distance(map.ideal_slot(k), i)))
+      .max()
+      .unwrap_or(0)
+  }
+
+  #[test]
+  fn weak_hashers_spread() {
+    type Identity = core::hash::BuildHasherDefault<IdentityHasher>;
+    let mut map: AllocHashMap<u64, u64, Identity> = AllocHashMap::new();
+    for i in 0..4000 {
+      map.insert(i, i);
+    }
+    assert!(longest_probe(&map) < 16, "probe run of {}", longest_probe(&map));
+
+    // Keys that only differ in their high bits spread too.
+    let mut map: AllocHashMap<u64, u64, Identity> = AllocHashMap::new();
+    for i in 0..4000 {
+      map.insert(i << 40, i);
+    }
+    assert!(longest_probe(&map) < 16, "probe run of {}", longest_probe(&map));
+    assert_eq!(map.get(&(3999 << 40)), Some(&3999));
+  }
+
+  #[test]
+  fn grows_and_shrinks() {
+    let mut map: AllocHashMap<u64, u64, RandomState> = AllocHashMap::with_capacity(10);
+    assert!(map.capacity() >= 10);
+    for i in 0..1000 {
+      map.insert(i, i * 2);
+    }
+    assert!(map.capacity() >= 1000);
+    for i in 10..1000 {
+      map.remove(&i);
+    }
+    map.shrink_to_fit();
+    assert!(map.capacity() < 100);
+    assert_eq!(map.len(), 10);
+    for (k, v) in map.iter() {
+      assert_eq!(*v, k * 2);
+    }
+    map.values_mut().for_each(|v| *v = 0);
+    assert_eq!(map.values().sum::<u64>(), 0);
+    assert_eq!(map.keys().count(), 10);
+
+    // Churning through removals doesn't grow the table.
+    let capacity = map.capacity();
+    for i in 0..10_000 {
+      map.insert(i + 100, i);
+      map.remove(&(i + 100));
+    }
+    assert_eq!(map.capacity(), capacity);
+  }
+}
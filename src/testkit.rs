@@ -0,0 +1,526 @@
+// Checks for anyone writing their own DLL or LRU backend, so it can be
+// held to the same behavior as the ones in this crate. Turn on the
+// testkit feature, then either call the check_ functions from your own
+// tests or have the macros write the tests for you:
+//
+//   lru_cache::dll_conformance!(MyList<u32>);
+//   lru_cache::lru_conformance!(Lru<u32, u32, MyList<(u32, u32)>>);
+//
+// The checks only use the DLL and LRU traits. A list with a max_size
+// is never pushed past it: check_dll only fills a small one up and
+// empties it again, and the random runs stop adding nodes once it's
+// full.
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use crate::error::Error;
+use crate::linked_list::DLL;
+use crate::lru::LRU;
+
+// SplitMix64. Plenty random enough to pick operations, and the same
+// seed always gives the same run, so a failure can be replayed.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+  pub fn new(seed: u64) -> Self {
+    Rng(seed)
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  // A number in 0..n. n has to be more than 0.
+  pub fn below(&mut self, n: usize) -> usize {
+    (self.next_u64() % n as u64) as usize
+  }
+}
+
+fn contents<L: DLL<u32>>(l: &L) -> Vec<u32> {
+  l.iter().map(|n| *n).collect()
+}
+
+// The pointer to the i'th node from the front. DLL pointers don't have
+// to be Clone, so the checks walk to a fresh one every time.
+fn nth<L: DLL<u32>>(l: &L, i: usize) -> L::Pointer {
+  let mut ptr = l.head().expect("nth on an empty list");
+  for _ in 0..i {
+    ptr = l.next_node(&ptr).expect("nth past the end of the list");
+  }
+  ptr
+}
+
+// Walks the list both ways, by hand and with iter, and checks it
+// against the model.
+fn assert_matches<L: DLL<u32>>(l: &L, model: &VecDeque<u32>, seed: u64, step: usize) {
+  let expected: Vec<u32> = model.iter().copied().collect();
+  assert_eq!(l.check_invariants(), Ok(()), "seed {seed} step {step}");
+  assert_eq!(l.size(), model.len(), "size, seed {seed} step {step}");
+  assert_eq!(contents(l), expected, "iter, seed {seed} step {step}");
+
+  let mut backwards = Vec::new();
+  let mut at = l.tail();
+  while let Some(ptr) = at {
+    backwards.push(*l.get(&ptr).unwrap());
+    at = l.prev_node(&ptr);
+  }
+  backwards.reverse();
+  assert_eq!(backwards, expected, "prev_node, seed {seed} step {step}");
+
+  assert_eq!(l.peek_front().as_deref(), model.front(), "peek_front, seed {seed} step {step}");
+  assert_eq!(l.peek_back().as_deref(), model.back(), "peek_back, seed {seed} step {step}");
+}
+
+// The most nodes check_dll has in one list at a time.
+const FULL_PASS: usize = 6;
+
+// For lists too small for the full pass. Fills the list up, checks it
+// turns away one more, and empties it again.
+fn check_bounded_dll<L: DLL<u32>>(max: usize) {
+  let mut l = L::new();
+  let mut model = VecDeque::new();
+  let mut next = 0;
+  while l.has_room(1) {
+    if next % 2 == 0 {
+      l.push_back(next);
+      model.push_back(next);
+    } else {
+      l.push_front(next);
+      model.push_front(next);
+    }
+    next += 1;
+    assert_matches(&l, &model, 0, next as usize);
+  }
+  assert_eq!(l.size(), max);
+  assert_eq!(l.try_push_back(next).err(), Some(Error::CapacityExceeded));
+  assert_eq!(l.try_push_front(next).err(), Some(Error::CapacityExceeded));
+  assert_matches(&l, &model, 0, max);
+
+  while let Some(elem) = l.pop_front() {
+    assert_eq!(Some(elem), model.pop_front());
+  }
+  assert!(l.head().is_none() && l.tail().is_none());
+  assert!(l.has_room(max));
+}
+
+// One pass through everything DLL promises, on small lists. A list
+// that can't hold FULL_PASS nodes only gets filled up and emptied.
+pub fn check_dll<L: DLL<u32>>() {
+  let mut l = L::new();
+  assert_eq!(l.size(), 0);
+  assert!(l.head().is_none() && l.tail().is_none());
+  assert!(l.peek_front().is_none() && l.peek_back().is_none());
+  assert_eq!(l.pop_front(), None);
+  assert_eq!(l.pop_back(), None);
+  assert_eq!(l.iter().next().as_deref(), None);
+  assert_eq!(l.iter_mut().len(), 0);
+
+  if let Some(max) = L::max_size().filter(|max| *max < FULL_PASS) {
+    check_bounded_dll::<L>(max);
+    return;
+  }
+
+  // Pushing at both ends, and getting through the pointers
+  let two = l.push_back(2);
+  let three = l.push_back(3);
+  let one = l.push_front(1);
+  assert_eq!(contents(&l), [1, 2, 3]);
+  assert_eq!(l.get(&one).as_deref(), Some(&1));
+  assert_eq!(l.get(&two).as_deref(), Some(&2));
+  assert_eq!(l.get(&three).as_deref(), Some(&3));
+  *l.get_mut(&two).unwrap() = 20;
+  l.replace_val(&three, 30);
+  assert_eq!(contents(&l), [1, 20, 30]);
+
+  // Walking between nodes stops at the ends
+  assert!(l.prev_node(&one).is_none());
+  assert!(l.next_node(&three).is_none());
+  let after_one = l.next_node(&one).unwrap();
+  assert_eq!(l.get(&after_one).as_deref(), Some(&20));
+
+  // Inserting next to a node
+  l.insert_before(&two, 15).unwrap();
+  l.insert_after(&two, 25).unwrap();
+  assert_eq!(contents(&l), [1, 15, 20, 25, 30]);
+
+  // Removed nodes can't be reached through their old pointer
+  assert_eq!(l.remove(&two), Some(20));
+  assert_eq!(l.remove(&two), None);
+  assert!(l.get(&two).is_none());
+  assert!(l.insert_after(&two, 99).is_none());
+  assert_eq!(contents(&l), [1, 15, 25, 30]);
+
+  // Moving nodes to either end
+  let mut ptr = nth(&l, 1);
+  l.move_back(&mut ptr);
+  assert_eq!(l.get(&ptr).as_deref(), Some(&15));
+  assert_eq!(contents(&l), [1, 25, 30, 15]);
+  let mut ptr = nth(&l, 2);
+  l.move_front(&mut ptr);
+  assert_eq!(l.get(&ptr).as_deref(), Some(&30));
+  assert_eq!(contents(&l), [30, 1, 25, 15]);
+
+  // Iterating from both ends, and changing everything in place
+  assert_eq!(l.iter().rev().map(|n| *n).collect::<Vec<_>>(), [15, 25, 1, 30]);
+  assert_eq!(l.iter().len(), 4);
+  let mut iter = l.iter_mut();
+  assert_eq!(iter.len(), 4);
+  *iter.next_back().unwrap() += 1;
+  for mut n in iter {
+    *n += 1;
+  }
+  assert_eq!(contents(&l), [31, 2, 26, 16]);
+
+  // Cursors wrap through the ghost past either end
+  let mut cursor = l.cursor_front();
+  assert_eq!(cursor.current().as_deref(), Some(&31));
+  cursor.move_prev();
+  assert!(cursor.current().is_none());
+  assert_eq!(cursor.peek_next().as_deref(), Some(&31));
+  assert_eq!(cursor.peek_prev().as_deref(), Some(&16));
+  cursor.move_prev();
+  assert_eq!(cursor.current().as_deref(), Some(&16));
+
+  let mut cursor = l.cursor_front_mut();
+  cursor.move_next();
+  assert_eq!(cursor.remove_current(), Some(2));
+  assert_eq!(cursor.current().as_deref(), Some(&26));
+  cursor.insert_before(3);
+  cursor.move_next();
+  cursor.move_next();
+  assert!(cursor.current().is_none());
+  cursor.insert_before(40);
+  cursor.insert_after(0);
+  assert_eq!(contents(&l), [0, 31, 3, 26, 16, 40]);
+
+  // Popping from both ends
+  assert_eq!(l.pop_front(), Some(0));
+  assert_eq!(l.pop_back(), Some(40));
+  assert_eq!(contents(&l), [31, 3, 26, 16]);
+
+  // Moving runs of nodes between lists
+  let mut rest = l.split_off(&nth(&l, 2));
+  assert_eq!(contents(&l), [31, 3]);
+  assert_eq!(contents(&rest), [26, 16]);
+  l.splice_after(&nth(&l, 0), &mut rest);
+  assert_eq!(rest.size(), 0);
+  assert_eq!(contents(&l), [31, 26, 16, 3]);
+  let mut other = L::new();
+  other.push_back(7);
+  other.push_back(8);
+  l.append(&mut other);
+  assert_eq!(other.size(), 0);
+  assert_eq!(contents(&l), [31, 26, 16, 3, 7, 8]);
+  let all = l.split_off(&nth(&l, 0));
+  assert_eq!(l.size(), 0);
+  assert_eq!(contents(&all), [31, 26, 16, 3, 7, 8]);
+
+  // Emptying it out leaves it usable
+  let mut l = all;
+  while l.pop_back().is_some() {}
+  assert_eq!(l.size(), 0);
+  assert!(l.head().is_none() && l.tail().is_none());
+  l.push_back(1);
+  assert_eq!(contents(&l), [1]);
+}
+
+// Runs steps random operations on the list and a VecDeque side by side,
+// checking they agree after every one.
+pub fn check_dll_against_vecdeque<L: DLL<u32>>(seed: u64, steps: usize) {
+  let mut rng = Rng::new(seed);
+  let mut l = L::new();
+  let mut model = VecDeque::new();
+  let mut next = 0;
+
+  for step in 0..steps {
+    let len = model.len();
+    let mut op = rng.below(16);
+    // Ops 2 to 8 need a node to work on.
+    if len == 0 && (2..9).contains(&op) {
+      op = 9;
+    }
+    // And 9 to 12 add nodes, so swap them for a removal when full, or
+    // a pop that finds nothing when the list can't hold any.
+    if (9..13).contains(&op) && !l.has_room(1) {
+      op = if len == 0 { 0 } else { 2 };
+    }
+
+    match op {
+      0 => assert_eq!(l.pop_front(), model.pop_front(), "pop_front, seed {seed} step {step}"),
+      1 => assert_eq!(l.pop_back(), model.pop_back(), "pop_back, seed {seed} step {step}"),
+      2 => {
+        let i = rng.below(len);
+        assert_eq!(l.remove(&nth(&l, i)), model.remove(i), "remove, seed {seed} step {step}");
+      },
+      3 => {
+        let i = rng.below(len);
+        let mut ptr = nth(&l, i);
+        l.move_back(&mut ptr);
+        let elem = model.remove(i).unwrap();
+        model.push_back(elem);
+        assert_eq!(l.get(&ptr).as_deref(), Some(&elem), "move_back, seed {seed} step {step}");
+      },
+      4 => {
+        let i = rng.below(len);
+        let mut ptr = nth(&l, i);
+        l.move_front(&mut ptr);
+        let elem = model.remove(i).unwrap();
+        model.push_front(elem);
+        assert_eq!(l.get(&ptr).as_deref(), Some(&elem), "move_front, seed {seed} step {step}");
+      },
+      5 => {
+        let i = rng.below(len);
+        l.replace_val(&nth(&l, i), next);
+        model[i] = next;
+        next += 1;
+      },
+      6 => {
+        let i = rng.below(len);
+        *l.get_mut(&nth(&l, i)).unwrap() += 1000;
+        model[i] += 1000;
+      },
+      7 => {
+        let i = rng.below(len);
+        let mut rest = l.split_off(&nth(&l, i));
+        let mut rest_model = model.split_off(i);
+        assert_matches(&l, &model, seed, step);
+        assert_matches(&rest, &rest_model, seed, step);
+        l.append(&mut rest);
+        model.append(&mut rest_model);
+      },
+      8 => {
+        for (mut n, m) in l.iter_mut().zip(model.iter_mut()) {
+          *n += 1;
+          *m += 1;
+        }
+      },
+      9 => {
+        l.push_back(next);
+        model.push_back(next);
+        next += 1;
+      },
+      10 => {
+        l.push_front(next);
+        model.push_front(next);
+        next += 1;
+      },
+      11 if len > 0 => {
+        let i = rng.below(len);
+        let ptr = if rng.below(2) == 0 {
+          model.insert(i, next);
+          l.insert_before(&nth(&l, i), next)
+        } else {
+          model.insert(i + 1, next);
+          l.insert_after(&nth(&l, i), next)
+        };
+        assert_eq!(l.get(&ptr.unwrap()).as_deref(), Some(&next), "insert, seed {seed} step {step}");
+        next += 1;
+      },
+      13 => {
+        l.reverse();
+        model.make_contiguous().reverse();
+      },
+      14 => {
+        // A small key so there are ties, which have to stay in order.
+        let m = rng.below(5) as u32 + 1;
+        l.sort_by(|a, b| (a % m).cmp(&(b % m)));
+        model.make_contiguous().sort_by_key(|a| a % m);
+      },
+      15 => {
+        let m = rng.below(5) as u32 + 2;
+        if rng.below(2) == 0 {
+          l.retain(|x| x % m != 0);
+        } else {
+          let drained: Vec<_> = l.drain_filter(|x| *x % m == 0).collect();
+          let expected: Vec<_> = model.iter().copied().filter(|x| x % m == 0).collect();
+          assert_eq!(drained, expected, "drain_filter, seed {seed} step {step}");
+        }
+        model.retain(|x| x % m != 0);
+      },
+      12 if len > 0 => {
+        let mut other = L::new();
+        let mut added = Vec::new();
+        for _ in 0..rng.below(4) {
+          if l.has_room(added.len() + 1) {
+            other.push_back(next);
+            added.push(next);
+            next += 1;
+          }
+        }
+        let i = rng.below(len);
+        l.splice_after(&nth(&l, i), &mut other);
+        assert_eq!(other.size(), 0, "splice_after, seed {seed} step {step}");
+        for (j, elem) in added.into_iter().enumerate() {
+          model.insert(i + 1 + j, elem);
+        }
+      },
+      _ => {
+        l.push_back(next);
+        model.push_back(next);
+        next += 1;
+      },
+    }
+
+    assert_matches(&l, &model, seed, step);
+  }
+}
+
+// One pass through what every LRU should do, with a capacity of 3.
+pub fn check_lru<C: LRU<u32, u32>>() {
+  let mut lru = C::new(3);
+  assert_eq!(lru.key_holder().size(), 0);
+  assert_eq!(lru.key_holder().capacity(), 3);
+  assert!(lru.get(&1).is_none());
+  assert_eq!(lru.remove(&1), None);
+
+  lru.put(1, 10);
+  lru.put(2, 20);
+  lru.put(3, 30);
+  assert_eq!(lru.key_holder().size(), 3);
+
+  // A full cache drops the least recently used entry
+  lru.put(4, 40);
+  assert!(lru.get(&1).is_none());
+  assert_eq!(lru.key_holder().size(), 3);
+
+  // get makes an entry the most recently used, peek doesn't
+  assert_eq!(lru.get(&2).as_deref(), Some(&20));
+  assert_eq!(lru.key_holder().peek(&3).as_deref(), Some(&30));
+  lru.put(5, 50);
+  assert!(lru.get(&3).is_none());
+  assert_eq!(lru.get(&2).as_deref(), Some(&20));
+
+  // Updating an entry doesn't evict anything, and makes it the most
+  // recently used
+  lru.put(4, 41);
+  assert_eq!(lru.key_holder().size(), 3);
+  let order: Vec<_> = lru.key_holder().iter().map(|entry| (entry.0, entry.1)).collect();
+  assert_eq!(order, [(5, 50), (2, 20), (4, 41)]);
+
+  // Removing an entry frees its slot
+  assert_eq!(lru.remove(&2), Some(20));
+  assert_eq!(lru.remove(&2), None);
+  lru.put(6, 60);
+  assert_eq!(lru.key_holder().size(), 3);
+  assert_eq!(lru.get(&5).as_deref(), Some(&50));
+  assert_eq!(lru.get(&4).as_deref(), Some(&41));
+  assert_eq!(lru.get(&6).as_deref(), Some(&60));
+
+  // A capacity of one keeps only the last entry
+  let mut lru = C::new(1);
+  lru.put(1, 10);
+  lru.put(2, 20);
+  assert!(lru.get(&1).is_none());
+  assert_eq!(lru.get(&2).as_deref(), Some(&20));
+}
+
+// Runs steps random operations on a cache and on a plain Vec kept in
+// order from least to most recently used, checking they agree after
+// every one. Keys are drawn from a little more than capacity, so there
+// are plenty of hits, misses and evictions.
+pub fn check_lru_against_model<C: LRU<u32, u32>>(seed: u64, capacity: usize, steps: usize) {
+  let mut rng = Rng::new(seed);
+  let mut lru = C::new(capacity);
+  let mut model: Vec<(u32, u32)> = Vec::new();
+  let keys = 2 * capacity + 1;
+
+  for step in 0..steps {
+    let key = rng.below(keys) as u32;
+    let found = model.iter().position(|(k, _)| *k == key);
+
+    match rng.below(8) {
+      0..=3 => {
+        let val = rng.next_u64() as u32;
+        lru.put(key, val);
+        if let Some(i) = found {
+          model.remove(i);
+        } else if model.len() == capacity {
+          model.remove(0);
+        }
+        model.push((key, val));
+      },
+      4 | 5 => {
+        let got = lru.get(&key).map(|val| *val);
+        let expected = found.map(|i| {
+          let entry = model.remove(i);
+          model.push(entry);
+          entry.1
+        });
+        assert_eq!(got, expected, "get, seed {seed} step {step}");
+      },
+      6 => {
+        let got = lru.key_holder().peek(&key).map(|val| *val);
+        assert_eq!(got, found.map(|i| model[i].1), "peek, seed {seed} step {step}");
+      },
+      _ => {
+        let expected = found.map(|i| model.remove(i).1);
+        assert_eq!(lru.remove(&key), expected, "remove, seed {seed} step {step}");
+      },
+    }
+
+    let holder = lru.key_holder();
+    assert_eq!(holder.check_invariants(), Ok(()), "seed {seed} step {step}");
+    assert_eq!(holder.size(), model.len(), "size, seed {seed} step {step}");
+    let order: Vec<_> = holder.iter().map(|entry| (entry.0, entry.1)).collect();
+    assert_eq!(order, model, "order, seed {seed} step {step}");
+  }
+}
+
+// Writes the DLL checks as tests, for a list of u32s.
+#[macro_export]
+macro_rules! dll_conformance {
+  ($type:ty) => {
+    #[cfg(test)]
+    mod dll_conformance {
+      use super::*;
+
+      #[test]
+      fn conformance() {
+        $crate::testkit::check_dll::<$type>();
+      }
+
+      #[test]
+      fn against_vecdeque() {
+        let seeds = if cfg!(miri) { 2 } else { 32 };
+        for seed in 0..seeds {
+          $crate::testkit::check_dll_against_vecdeque::<$type>(seed, 300);
+        }
+      }
+    }
+  };
+}
+
+// Writes the LRU checks as tests, for a cache from u32s to u32s. The
+// random runs use a few capacities, up to what the list can hold.
+#[macro_export]
+macro_rules! lru_conformance {
+  ($type:ty) => {
+    #[cfg(test)]
+    mod lru_conformance {
+      use super::*;
+      use $crate::linked_list::DLL;
+      use $crate::lru::LRU;
+
+      #[test]
+      fn conformance() {
+        $crate::testkit::check_lru::<$type>();
+      }
+
+      #[test]
+      fn against_model() {
+        let max = <<$type as LRU<u32, u32>>::List as DLL<(u32, u32)>>::max_size();
+        let seeds = if cfg!(miri) { 2 } else { 16 };
+        for capacity in [1, 3, 8, 32] {
+          let capacity = max.map_or(capacity, |max| capacity.min(max));
+          for seed in 0..seeds {
+            $crate::testkit::check_lru_against_model::<$type>(seed, capacity, 300);
+          }
+        }
+      }
+    }
+  };
+}
@@ -0,0 +1,85 @@
+// Checks that the array backed list and LRU stay off the heap once they
+// are built. This needs its own global allocator, so it lives in its
+// own test binary.
+use lru_cache::linked_list::DLL;
+use lru_cache::linked_list::arraylist::ArrayLinkedList;
+use lru_cache::lru::LRU;
+use lru_cache::lru::arraylru::ArrayLRU;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAlloc;
+
+thread_local! {
+  static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations_during<F: FnOnce()>(f: F) -> usize {
+  let before = ALLOCATIONS.with(Cell::get);
+  f();
+  ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn list_never_allocates() {
+  let count = allocations_during(|| {
+    let mut l: ArrayLinkedList<u64, 64> = ArrayLinkedList::new();
+    let mut ptrs = [None; 64];
+    for round in 0..100u64 {
+      for (i, ptr) in ptrs.iter_mut().enumerate() {
+        if let Some(p) = ptr.take() {
+          l.remove(&p);
+        }
+        if !(i as u64 + round).is_multiple_of(3) {
+          *ptr = l.try_push_back(round).ok();
+        }
+      }
+      for n in l.iter_mut() {
+        *n += 1;
+      }
+      assert!(l.try_push_front(0).is_ok() || l.is_full());
+      l.pop_front();
+    }
+  });
+
+  assert_eq!(count, 0);
+}
+
+#[test]
+fn lru_never_allocates_after_new() {
+  let mut lru: ArrayLRU<u32, u32, 64> = ArrayLRU::new(64);
+
+  let count = allocations_during(|| {
+    for i in 0..10_000u32 {
+      let key = i.wrapping_mul(2_654_435_761) % 300;
+      if lru.get(&key).is_none() {
+        lru.put(key, i);
+      }
+      if i.is_multiple_of(7) {
+        lru.remove(&(key / 2));
+      }
+    }
+  });
+
+  assert_eq!(count, 0);
+  assert_eq!(lru.key_holder().size(), 64);
+}
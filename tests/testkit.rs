@@ -0,0 +1,30 @@
+// The testkit the way a crate with its own backend would use it, from
+// outside. Run with --features testkit.
+use lru_cache::linked_list::veclist::CompactLinkedList;
+use lru_cache::lru::Lru;
+use lru_cache::map::FxBuildHasher;
+
+type FxCompactLru<K, T> = Lru<K, T, CompactLinkedList<(K, T)>, FxBuildHasher>;
+
+lru_cache::dll_conformance!(CompactLinkedList<u32>);
+lru_cache::lru_conformance!(FxCompactLru<u32, u32>);
+
+// Lists with a max_size smaller than the checks would like to use.
+mod bounded {
+  use lru_cache::linked_list::arraylist::ArrayLinkedList;
+
+  mod four {
+    use super::*;
+    lru_cache::dll_conformance!(ArrayLinkedList<u32, 4>);
+  }
+
+  mod one {
+    use super::*;
+    lru_cache::dll_conformance!(ArrayLinkedList<u32, 1>);
+  }
+
+  mod empty {
+    use super::*;
+    lru_cache::dll_conformance!(ArrayLinkedList<u32, 0>);
+  }
+}